@@ -0,0 +1,92 @@
+//! Measures the insert throughput and the memory held by an [`Organizer`] fed with a
+//! generated fork workload, for several allowed depths, and the time it takes to insert
+//! the same workload as a single batch.
+//!
+//! Run with `cargo bench --bench insert`, optionally followed by `-- <nodes> <seed>`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use abandoning_reorg::{Organizer, ReorgNode};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::workload::{Workload, WorkloadConfig};
+
+/// Allocator counting the bytes currently allocated, and the most ever allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const DEPTHS: [u64; 5] = [16, 64, 255, 1024, 4096];
+
+fn main() {
+    // Arguments that are not numbers, like the --bench flag cargo passes, are ignored
+    let mut numbers = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse::<u64>().ok());
+    let nodes = numbers.next().unwrap_or(100_000) as usize;
+    let seed = numbers.next().unwrap_or(42);
+    let config = WorkloadConfig {
+        nodes,
+        ..WorkloadConfig::default()
+    };
+    let workload: Vec<ReorgNode<u64, ()>> = Workload::new(config, seed).collect();
+    println!("{} nodes, seed {}", nodes, seed);
+    println!(
+        "{:>8} {:>12} {:>14} {:>12} {:>12} {:>12}",
+        "depth", "seconds", "nodes/s", "peak KiB", "final KiB", "batch s"
+    );
+    for depth in DEPTHS.iter() {
+        // The copy of the workload is counted in the peak, but it is freed by the
+        // end, so that what is left is held by the organizer
+        let base = ALLOCATED.load(Ordering::Relaxed);
+        let nodes = workload.clone();
+        PEAK.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+        let start = Instant::now();
+        let mut organizer = Organizer::new_with(Workload::genesis(), *depth, false);
+        for node in nodes {
+            organizer.insert(node, None);
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        let held = ALLOCATED.load(Ordering::Relaxed).saturating_sub(base);
+        let peak = PEAK.load(Ordering::Relaxed) - base;
+        drop(organizer);
+        let nodes = workload.clone();
+        let start = Instant::now();
+        let mut organizer = Organizer::new_with(Workload::genesis(), *depth, false);
+        organizer.insert_batch(nodes, None);
+        let batch = start.elapsed().as_secs_f64();
+        println!(
+            "{:>8} {:>12.3} {:>14.0} {:>12} {:>12} {:>12.3}",
+            depth,
+            elapsed,
+            workload.len() as f64 / elapsed,
+            peak / 1024,
+            held / 1024,
+            batch
+        );
+    }
+}
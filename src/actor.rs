@@ -0,0 +1,318 @@
+//! Actor that owns an [`Organizer`] on its own thread, and is driven by commands
+//! sent over a [`std::sync::mpsc`] channel. Subscribers receive an [`Event`] for
+//! every finalized and abandoned node, every change of the canonical head and
+//! every misbehaving source.
+
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, JoinHandle};
+
+use crate::{
+    InsertOutcome, MemoryStore, Misbehavior, NodeStore, Observer, Organizer, ReorgNode, Weight,
+};
+
+/// Query run by the actor on the organizer it owns.
+type Query<K, M, S, V> = Box<dyn FnOnce(&Organizer<K, M, S, V>) + Send>;
+
+/// Nodes abandoned by a finalization, or none if the node was unknown.
+type Abandoned<K, M, V> = Option<Vec<ReorgNode<K, M, V>>>;
+
+/// Reply channel of a finalization, receiving the abandoned nodes.
+type FinalizeReply<K, M, V> = Sender<Abandoned<K, M, V>>;
+
+/// Command understood by the [`Actor`].
+pub enum Command<K, M, S = MemoryStore<K, M>, V: Weight = u64> {
+    /// Inserts the node, see [`Organizer::insert`], replying with the outcome if a
+    /// sender is given.
+    Insert {
+        node: ReorgNode<K, M, V>,
+        most_valuable: Option<bool>,
+        reply: Option<Sender<InsertOutcome<K>>>,
+    },
+    /// Finalizes the node, see [`Organizer::finalize`], replying with the abandoned
+    /// nodes if a sender is given.
+    Finalize {
+        key: K,
+        reply: Option<FinalizeReply<K, M, V>>,
+    },
+    /// Runs the function on the organizer.
+    Query(Query<K, M, S, V>),
+    /// Sends every event from now on to the sender.
+    Subscribe(Sender<Event<K>>),
+    /// Stops the actor, even if handles to it are still alive.
+    Stop,
+}
+
+/// Change of the tree broadcast to the subscribers of the [`Actor`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Event<K> {
+    /// The node became the root.
+    Finalized(K),
+    /// The node was removed with an abandoned branch.
+    Abandoned(K),
+    /// The canonical chain was extended to a new head.
+    NewHead(K),
+    /// The canonical chain switched to another branch.
+    Reorg {
+        /// The head of the canonical chain before the switch.
+        old_head: K,
+        /// The head of the canonical chain after the switch.
+        new_head: K,
+    },
+    /// The source reached the misbehavior threshold, see [`Observer::misbehaved`].
+    Misbehaved(u64),
+}
+
+/// The [`Actor`] is no longer running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Stopped;
+
+impl Display for Stopped {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "the actor is no longer running")
+    }
+}
+
+impl Error for Stopped {}
+
+/// Observer collecting the finalized and abandoned nodes into events, as long as
+/// the actor is running.
+struct Collector<K>(Weak<Mutex<Vec<Event<K>>>>);
+
+impl<K> Collector<K> {
+    /// Queues the event, unless the actor has already stopped.
+    fn push(&self, event: Event<K>) {
+        if let Some(events) = self.0.upgrade() {
+            events.lock().unwrap().push(event);
+        }
+    }
+}
+
+impl<K: Copy, M, V: Weight> Observer<K, M, V> for Collector<K> {
+    fn finalized(&mut self, node: &ReorgNode<K, M, V>) {
+        self.push(Event::Finalized(node.key));
+    }
+
+    fn abandoned(&mut self, node: &ReorgNode<K, M, V>) {
+        self.push(Event::Abandoned(node.key));
+    }
+
+    fn misbehaved(&mut self, source: u64, _misbehavior: &Misbehavior) {
+        self.push(Event::Misbehaved(source));
+    }
+}
+
+/// Cloneable handle that sends commands to an [`Actor`].
+pub struct ActorHandle<K, M, S = MemoryStore<K, M>, V: Weight = u64> {
+    sender: Sender<Command<K, M, S, V>>,
+}
+
+impl<K, M, S, V: Weight> Clone for ActorHandle<K, M, S, V> {
+    fn clone(&self) -> Self {
+        ActorHandle {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<K, M, S, V> ActorHandle<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy + Send + 'static,
+    M: Debug + Default + Send + 'static,
+    S: NodeStore<K, M> + Send + 'static,
+    V: Weight + Send + 'static,
+    V::Sum: Send,
+{
+    /// Sends a raw command to the actor.
+    pub fn send(&self, command: Command<K, M, S, V>) -> Result<(), Stopped> {
+        self.sender.send(command).map_err(|_| Stopped)
+    }
+
+    /// Inserts the node without waiting for the outcome.
+    pub fn submit(
+        &self,
+        node: ReorgNode<K, M, V>,
+        most_valuable: Option<bool>,
+    ) -> Result<(), Stopped> {
+        self.send(Command::Insert {
+            node,
+            most_valuable,
+            reply: None,
+        })
+    }
+
+    /// Inserts the node, waiting for the outcome.
+    pub fn insert(
+        &self,
+        node: ReorgNode<K, M, V>,
+        most_valuable: Option<bool>,
+    ) -> Result<InsertOutcome<K>, Stopped> {
+        let (reply, outcome) = mpsc::channel();
+        self.send(Command::Insert {
+            node,
+            most_valuable,
+            reply: Some(reply),
+        })?;
+        outcome.recv().map_err(|_| Stopped)
+    }
+
+    /// Finalizes the node, waiting for the abandoned nodes.
+    pub fn finalize(&self, key: K) -> Result<Abandoned<K, M, V>, Stopped> {
+        let (reply, abandoned) = mpsc::channel();
+        self.send(Command::Finalize {
+            key,
+            reply: Some(reply),
+        })?;
+        abandoned.recv().map_err(|_| Stopped)
+    }
+
+    /// Runs the function on the organizer, waiting for its result.
+    pub fn query<T, F>(&self, f: F) -> Result<T, Stopped>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Organizer<K, M, S, V>) -> T + Send + 'static,
+    {
+        let (reply, result) = mpsc::channel();
+        self.send(Command::Query(Box::new(move |organizer| {
+            // The caller might have given up on the result
+            let _ = reply.send(f(organizer));
+        })))?;
+        result.recv().map_err(|_| Stopped)
+    }
+
+    /// Returns a receiver of every event from now on.
+    pub fn subscribe(&self) -> Result<Receiver<Event<K>>, Stopped> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Command::Subscribe(sender))?;
+        Ok(receiver)
+    }
+}
+
+/// Thread owning an [`Organizer`], processing the commands in the order they arrive.
+pub struct Actor<K, M, S = MemoryStore<K, M>, V: Weight = u64> {
+    handle: ActorHandle<K, M, S, V>,
+    thread: JoinHandle<Organizer<K, M, S, V>>,
+}
+
+impl<K, M, S, V> Actor<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy + Send + 'static,
+    M: Debug + Default + Send + 'static,
+    S: NodeStore<K, M> + Send + 'static,
+    V: Weight + Send + 'static,
+    V::Sum: Send,
+{
+    /// Moves the organizer onto a new thread that runs until it is stopped, or
+    /// until the actor and every handle are dropped.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Actor, Event, Organizer, ReorgNode};
+    ///
+    /// let organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// let actor = Actor::spawn(organizer);
+    /// let handle = actor.handle();
+    /// let events = handle.subscribe().unwrap();
+    /// handle.insert(ReorgNode::new(2, 1, 0, 1, ()), None).unwrap();
+    /// assert_eq!(events.recv().unwrap(), Event::NewHead(2));
+    /// assert_eq!(handle.query(|organizer| organizer.height()).unwrap(), 1);
+    /// let organizer = actor.stop();
+    /// assert_eq!(organizer.height(), 1);
+    /// ```
+    pub fn spawn(mut organizer: Organizer<K, M, S, V>) -> Self {
+        let (sender, commands) = mpsc::channel();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let collector = organizer.observers.len();
+        organizer.add_observer(Collector(Arc::downgrade(&events)));
+        let thread = thread::spawn(move || {
+            let mut subscribers = Vec::new();
+            for command in commands {
+                // Only the commands changing the tree can move the canonical head
+                let old_head = match command {
+                    Command::Insert { .. } | Command::Finalize { .. } => {
+                        Some(organizer.current_head())
+                    }
+                    _ => None,
+                };
+                // Replies are sent after the events, so that a caller waiting for the
+                // reply finds the events of the command already queued.
+                let reply: Option<Box<dyn FnOnce()>> = match command {
+                    Command::Insert {
+                        node,
+                        most_valuable,
+                        reply,
+                    } => {
+                        let outcome = organizer.insert(node, most_valuable);
+                        reply.map(|reply| {
+                            Box::new(move || {
+                                let _ = reply.send(outcome);
+                            }) as Box<dyn FnOnce()>
+                        })
+                    }
+                    Command::Finalize { key, reply } => {
+                        let abandoned = organizer.finalize(&key);
+                        reply.map(|reply| {
+                            Box::new(move || {
+                                let _ = reply.send(abandoned);
+                            }) as Box<dyn FnOnce()>
+                        })
+                    }
+                    Command::Query(query) => {
+                        query(&organizer);
+                        None
+                    }
+                    Command::Subscribe(subscriber) => {
+                        subscribers.push(subscriber);
+                        None
+                    }
+                    Command::Stop => break,
+                };
+                let mut events = std::mem::take(&mut *events.lock().unwrap());
+                if let Some(old_head) = old_head {
+                    let new_head = organizer.current_head();
+                    if new_head != old_head {
+                        // An abandoned head is left behind as well
+                        let reorg = organizer.get_topology(&old_head).is_none()
+                            || organizer.fork_depth(old_head, new_head) > 0;
+                        events.push(if reorg {
+                            Event::Reorg { old_head, new_head }
+                        } else {
+                            Event::NewHead(new_head)
+                        });
+                    }
+                }
+                // Subscribers that dropped their receivers are forgotten
+                for event in events {
+                    subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+                }
+                if let Some(reply) = reply {
+                    reply();
+                }
+            }
+            // The organizer is handed back without the collector of the events
+            organizer.observers.remove(collector);
+            organizer
+        });
+        Actor {
+            handle: ActorHandle { sender },
+            thread,
+        }
+    }
+
+    /// Returns a handle to send commands with.
+    pub fn handle(&self) -> ActorHandle<K, M, S, V> {
+        self.handle.clone()
+    }
+
+    /// Stops the actor after the commands sent before, and returns the organizer.
+    /// # Panics
+    /// If the thread of the actor panicked.
+    pub fn stop(self) -> Organizer<K, M, S, V> {
+        let _ = self.handle.send(Command::Stop);
+        self.thread
+            .join()
+            .expect("the thread of the actor panicked")
+    }
+}
@@ -0,0 +1,229 @@
+//! Simulator that drives an [`Organizer`] through a scenario file, so that fork
+//! scenarios can be reproduced without writing Rust.
+//!
+//! Usage: `reorg-sim [--depth <depth>] [--value-based] <scenario file, or - for stdin>`
+//!
+//! Every line of the scenario is one of the following, `#` starting a comment:
+//! - `root <key> <height>` sets the first root, only as the first command. Without it
+//!   the organizer starts in forest mode.
+//! - `insert <key> <height> <value> <parent>` inserts a node.
+//! - `finalize <key>` finalizes a node.
+//! - `dump` prints the tree.
+//!
+//! The outcome of every command, the finalized and abandoned nodes and the switches of
+//! the canonical chain are printed, followed by the final tree.
+
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::process;
+
+use abandoning_reorg::{InsertOutcome, Observer, Organizer, ReorgNode};
+
+/// Greatest length of a key in bytes.
+const KEY_CAPACITY: usize = 64;
+
+/// Key of the nodes of the scenario, a short string that can be copied.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    len: u8,
+    bytes: [u8; KEY_CAPACITY],
+}
+
+impl Key {
+    fn parse(text: &str) -> Result<Key, String> {
+        if text.len() > KEY_CAPACITY {
+            return Err(format!(
+                "the key {} is longer than {} bytes",
+                text, KEY_CAPACITY
+            ));
+        }
+        let mut key = Key::default();
+        key.bytes[..text.len()].copy_from_slice(text.as_bytes());
+        key.len = text.len() as u8;
+        Ok(key)
+    }
+
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len as usize]).expect("keys are parsed from text")
+    }
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Key {
+            len: 0,
+            bytes: [0; KEY_CAPACITY],
+        }
+    }
+}
+
+impl Debug for Key {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Prints the finalized and abandoned nodes.
+struct Printer;
+
+impl Observer<Key, ()> for Printer {
+    fn finalized(&mut self, node: &ReorgNode<Key, ()>) {
+        println!("  finalized {:?}", node.key());
+    }
+
+    fn abandoned(&mut self, node: &ReorgNode<Key, ()>) {
+        println!("  abandoned {:?}", node.key());
+    }
+}
+
+/// Settings read from the command line.
+struct Options {
+    depth: u64,
+    value_based: bool,
+    scenario: String,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: reorg-sim [--depth <depth>] [--value-based] <scenario file, or - for stdin>");
+    process::exit(2);
+}
+
+fn parse_options() -> Options {
+    let mut options = Options {
+        depth: 255,
+        value_based: false,
+        scenario: String::new(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => match args.next().and_then(|depth| depth.parse().ok()) {
+                Some(depth) if depth > 0 => options.depth = depth,
+                _ => usage(),
+            },
+            "--value-based" => options.value_based = true,
+            _ if options.scenario.is_empty() => options.scenario = arg,
+            _ => usage(),
+        }
+    }
+    if options.scenario.is_empty() {
+        usage();
+    }
+    options
+}
+
+fn parse_number(text: &str) -> Result<u64, String> {
+    text.parse()
+        .map_err(|_| format!("{} is not a non-negative number", text))
+}
+
+fn dump(organizer: &Organizer<Key, ()>) {
+    let mut tree = String::new();
+    organizer
+        .write_tree(&mut tree)
+        .expect("writing into a String does not fail");
+    print!("{}", tree);
+}
+
+/// Runs a line of the scenario, returning the organizer created by a `root` command.
+fn run(
+    organizer: &mut Organizer<Key, ()>,
+    words: &[&str],
+    first: bool,
+    options: &Options,
+) -> Result<Option<Organizer<Key, ()>>, String> {
+    match words {
+        ["root", key, height] => {
+            if !first {
+                return Err("the root can only be set by the first command".to_string());
+            }
+            let root = ReorgNode::new(
+                Key::parse(key)?,
+                parse_number(height)?,
+                0,
+                Key::default(),
+                (),
+            );
+            let mut organizer = Organizer::new_with(root, options.depth, options.value_based);
+            organizer.add_observer(Printer);
+            println!("root {}", key);
+            return Ok(Some(organizer));
+        }
+        ["insert", key, height, value, parent] => {
+            let node = ReorgNode::new(
+                Key::parse(key)?,
+                parse_number(height)?,
+                parse_number(value)?,
+                Key::parse(parent)?,
+                (),
+            );
+            let (old_head, reorgs) = (organizer.canonical_head(), organizer.stats().reorgs);
+            println!("insert {}", key);
+            match organizer.insert(node, None) {
+                InsertOutcome::Attached { .. } => println!("  attached"),
+                InsertOutcome::Buffered => println!("  buffered"),
+                InsertOutcome::Rejected(rejection) => println!("  rejected: {:?}", rejection),
+            }
+            let new_head = organizer.canonical_head();
+            // The organizer counts the switches of the canonical chain itself
+            if organizer.stats().reorgs > reorgs {
+                println!("  reorg from {:?} to {:?}", old_head, new_head);
+            } else if new_head != old_head {
+                println!("  new head {:?}", new_head);
+            }
+        }
+        ["finalize", key] => {
+            println!("finalize {}", key);
+            if organizer.finalize(&Key::parse(key)?).is_none() {
+                println!("  unknown node");
+            }
+        }
+        ["dump"] => dump(organizer),
+        _ => return Err(format!("unknown command: {}", words.join(" "))),
+    }
+    Ok(None)
+}
+
+fn main() {
+    let options = parse_options();
+    let reader: Box<dyn BufRead> = if options.scenario == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        match File::open(&options.scenario) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(error) => {
+                eprintln!("failed to open {}: {}", options.scenario, error);
+                process::exit(1);
+            }
+        }
+    };
+    let mut organizer = Organizer::new_forest(options.depth, options.value_based);
+    organizer.add_observer(Printer);
+    let mut first = true;
+    for (number, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                eprintln!("failed to read the scenario: {}", error);
+                process::exit(1);
+            }
+        };
+        let line = line.split('#').next().unwrap_or("");
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        match run(&mut organizer, &words, first, &options) {
+            Ok(Some(rooted)) => organizer = rooted,
+            Ok(None) => {}
+            Err(message) => {
+                eprintln!("line {}: {}", number + 1, message);
+                process::exit(1);
+            }
+        }
+        first = false;
+    }
+    println!("final tree");
+    dump(&organizer);
+}
@@ -0,0 +1,238 @@
+//! Builder that gathers every configuration of an [`Organizer`] in one place,
+//! and validates them before the organizer is created.
+
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
+
+use crate::{
+    ForkChoice, MemoryStore, NodeStore, Observer, Organizer, ReorgNode, TieBreaker, Validator,
+    Weight, DEFAULT_BUFFER_LIMIT, DEFAULT_MAX_ORPHAN_DISTANCE,
+};
+
+/// Configuration the [`OrganizerBuilder`] refused to build from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildError {
+    /// The allowed depth is zero, so no branch could ever be kept.
+    ZeroDepth,
+    /// Neither an initial root, nor the forest mode was set.
+    MissingRoot,
+    /// Both an initial root and the forest mode was set.
+    RootInForest,
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            BuildError::ZeroDepth => write!(f, "the allowed depth must be greater than zero"),
+            BuildError::MissingRoot => write!(f, "no initial root was set outside of forest mode"),
+            BuildError::RootInForest => write!(f, "an initial root was set in forest mode"),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Builder for the [`Organizer`], created by [`Organizer::builder`].
+pub struct OrganizerBuilder<K, M, S = MemoryStore<K, M>, V: Weight = u64> {
+    allowed_depth: u64,
+    fork_choice: ForkChoice,
+    tie_breaker: TieBreaker<K>,
+    buffer_limit: usize,
+    max_orphan_distance: u64,
+    misbehavior_threshold: Option<u64>,
+    validators: Vec<Validator<K, M, V>>,
+    observers: Vec<Box<dyn Observer<K, M, V> + Send + Sync>>,
+    root: Option<ReorgNode<K, M, V>>,
+    forest: bool,
+    slot_based: bool,
+    store: S,
+}
+
+impl<K, M, S: Default, V: Weight> Default for OrganizerBuilder<K, M, S, V> {
+    fn default() -> Self {
+        OrganizerBuilder {
+            allowed_depth: 255,
+            fork_choice: ForkChoice::Longest,
+            tie_breaker: TieBreaker::FirstSeen,
+            buffer_limit: DEFAULT_BUFFER_LIMIT,
+            max_orphan_distance: DEFAULT_MAX_ORPHAN_DISTANCE,
+            misbehavior_threshold: None,
+            validators: Vec::new(),
+            observers: Vec::new(),
+            root: None,
+            forest: false,
+            slot_based: false,
+            store: S::default(),
+        }
+    }
+}
+
+impl<K, M, S, V> OrganizerBuilder<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy,
+    M: Debug + Default,
+    S: NodeStore<K, M>,
+    V: Weight,
+{
+    /// Creates a builder with the same defaults as [`Organizer::default`], but without a root.
+    pub fn new() -> Self
+    where
+        S: Default,
+    {
+        Self::default()
+    }
+
+    /// Sets the depth we allow reorganization to.
+    pub fn depth(mut self, allowed_depth: u64) -> Self {
+        self.allowed_depth = allowed_depth;
+        self
+    }
+
+    /// Sets the rule by which the leading branch is decided.
+    pub fn fork_choice(mut self, fork_choice: ForkChoice) -> Self {
+        self.fork_choice = fork_choice;
+        self
+    }
+
+    /// Sets the rule by which equally worthy branches are decided between.
+    pub fn tie_breaker(mut self, tie_breaker: TieBreaker<K>) -> Self {
+        self.tie_breaker = tie_breaker;
+        self
+    }
+
+    /// Limits the number of nodes waiting for their parents in the buffer,
+    /// [`DEFAULT_BUFFER_LIMIT`] by default.
+    pub fn buffer_limit(mut self, limit: usize) -> Self {
+        self.buffer_limit = limit;
+        self
+    }
+
+    /// Limits how much greater the height of a buffered node may be than the current height,
+    /// [`DEFAULT_MAX_ORPHAN_DISTANCE`] by default.
+    pub fn max_orphan_distance(mut self, distance: u64) -> Self {
+        self.max_orphan_distance = distance;
+        self
+    }
+
+    /// Sets the total of useless submissions at which a source is reported to the
+    /// observers, see [`Observer::misbehaved`].
+    pub fn misbehavior_threshold(mut self, threshold: u64) -> Self {
+        self.misbehavior_threshold = Some(threshold);
+        self
+    }
+
+    /// Adds a validator every inserted node has to pass.
+    pub fn validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&ReorgNode<K, M, V>) -> bool + Send + Sync + 'static,
+    {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    /// Adds an observer that is notified about every finalized and abandoned node.
+    pub fn observer<O>(mut self, observer: O) -> Self
+    where
+        O: Observer<K, M, V> + Send + Sync + 'static,
+    {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Sets the first root node, possibly the genesis node.
+    pub fn root(mut self, root: ReorgNode<K, M, V>) -> Self {
+        self.root = Some(root);
+        self
+    }
+
+    /// Starts the organizer in forest mode instead of from a single root.
+    /// See [`Organizer::new_forest`].
+    pub fn forest(mut self) -> Self {
+        self.forest = true;
+        self
+    }
+
+    /// Lets every tip of the tree compete for the lead, for heights that are slots some
+    /// of which are skipped, so that a child may be several heights above its parent.
+    /// Otherwise only the nodes at the greatest height do, which is cheaper, and enough
+    /// when every height is taken. The allowed depth counts heights either way, from the
+    /// canonical head in slot based mode, and the root advances past every height below it.
+    /// [`ForkChoice::Longest`] counts the nodes of the branches, [`ForkChoice::MostSlots`]
+    /// counts their heights.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{ForkChoice, Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::builder()
+    ///     .slot_based()
+    ///     .root(ReorgNode::new(1, 100, 0, 0, ()))
+    ///     .build()
+    ///     .unwrap();
+    /// // One branch skips slots, the other holds more nodes below it
+    /// organizer.insert(ReorgNode::new(2, 101, 0, 1, ()), None);
+    /// organizer.insert(ReorgNode::new(3, 110, 0, 2, ()), None);
+    /// organizer.insert(ReorgNode::new(4, 101, 0, 1, ()), None);
+    /// organizer.insert(ReorgNode::new(5, 102, 0, 4, ()), None);
+    /// organizer.insert(ReorgNode::new(6, 103, 0, 5, ()), None);
+    /// assert_eq!(organizer.canonical_head(), 6);
+    /// organizer.set_fork_choice(ForkChoice::MostSlots);
+    /// assert_eq!(organizer.canonical_head(), 3);
+    /// ```
+    pub fn slot_based(mut self) -> Self {
+        self.slot_based = true;
+        self
+    }
+
+    /// Sets the store the custom meta data of the nodes is kept in.
+    /// See [`Organizer::with_store`].
+    pub fn store<T: NodeStore<K, M>>(self, store: T) -> OrganizerBuilder<K, M, T, V> {
+        OrganizerBuilder {
+            allowed_depth: self.allowed_depth,
+            fork_choice: self.fork_choice,
+            tie_breaker: self.tie_breaker,
+            buffer_limit: self.buffer_limit,
+            max_orphan_distance: self.max_orphan_distance,
+            misbehavior_threshold: self.misbehavior_threshold,
+            validators: self.validators,
+            observers: self.observers,
+            root: self.root,
+            forest: self.forest,
+            slot_based: self.slot_based,
+            store,
+        }
+    }
+
+    /// Creates the configured organizer, or returns why the configuration makes no sense.
+    pub fn build(mut self) -> Result<Organizer<K, M, S, V>, BuildError> {
+        if self.allowed_depth == 0 {
+            return Err(BuildError::ZeroDepth);
+        }
+        let root = match (self.root.take(), self.forest) {
+            (Some(_), true) => return Err(BuildError::RootInForest),
+            (None, false) => return Err(BuildError::MissingRoot),
+            (root, _) => root,
+        };
+        let mut organizer = self.into_organizer();
+        match root {
+            Some(root) => organizer.init(root),
+            None => organizer.start_forest(),
+        }
+        Ok(organizer)
+    }
+
+    /// Creates an organizer with a default root that holds every configuration of
+    /// the builder.
+    pub(crate) fn into_organizer(self) -> Organizer<K, M, S, V> {
+        let mut organizer = Organizer::with_store(self.store, self.allowed_depth);
+        organizer.fork_choice = self.fork_choice;
+        organizer.tie_breaker = self.tie_breaker;
+        organizer.buffer_limit = self.buffer_limit;
+        organizer.max_orphan_distance = self.max_orphan_distance;
+        organizer.misbehavior_threshold = self.misbehavior_threshold;
+        organizer.validators = self.validators;
+        organizer.observers = self.observers;
+        organizer.slot_based = self.slot_based;
+        organizer
+    }
+}
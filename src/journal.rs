@@ -0,0 +1,392 @@
+//! Append-only journal of the operations of an [`Organizer`], so that the reorg
+//! window can be persisted cheaply and rebuilt after a crash.
+//! The journal starts with a snapshot of the state, followed by a record for every
+//! insert, refused insert, batch insert, root advance, branch deletion, rewind, forgiven
+//! source and change of the root or fork choice rule. Every record is framed by its length and a CRC-32 checksum of the length,
+//! and followed by a CRC-32 checksum of its body.
+
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+
+use crate::snapshot::{decode_len, decode_node, encode_node};
+use crate::{
+    Codec, ForkChoice, NodeStore, Organizer, OrganizerBuilder, Rejection, ReorgNode, SnapshotError,
+    Weight,
+};
+
+const SNAPSHOT: u8 = 0;
+const INSERT: u8 = 1;
+const ROOT: u8 = 2;
+const DELETE: u8 = 3;
+const REWIND: u8 = 4;
+const INIT: u8 = 5;
+const FORK_CHOICE: u8 = 6;
+const BATCH: u8 = 7;
+const REJECT: u8 = 8;
+const FORGIVE: u8 = 9;
+
+/// Size of the length prefix and the checksums around the body of a record.
+const FRAME: usize = 12;
+
+/// Reason for which a journal could not be replayed.
+#[derive(Debug)]
+pub enum JournalError {
+    /// Reading the journal failed.
+    Io(io::Error),
+    /// The snapshot in the journal could not be restored.
+    Snapshot(SnapshotError),
+    /// The journal does not start with a snapshot.
+    MissingSnapshot,
+    /// A record that is followed by others is damaged, at the designated byte offset.
+    Corrupted(u64),
+}
+
+impl Display for JournalError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            JournalError::Io(error) => write!(f, "failed to read the journal: {}", error),
+            JournalError::Snapshot(error) => {
+                write!(f, "invalid snapshot in the journal: {}", error)
+            }
+            JournalError::MissingSnapshot => {
+                write!(f, "the journal does not start with a snapshot")
+            }
+            JournalError::Corrupted(offset) => {
+                write!(f, "the journal is corrupted at byte {}", offset)
+            }
+        }
+    }
+}
+
+impl Error for JournalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JournalError::Io(error) => Some(error),
+            JournalError::Snapshot(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for JournalError {
+    fn from(error: io::Error) -> Self {
+        JournalError::Io(error)
+    }
+}
+
+impl From<SnapshotError> for JournalError {
+    fn from(error: SnapshotError) -> Self {
+        JournalError::Snapshot(error)
+    }
+}
+
+/// Organizer rebuilt from a journal, with the length of the journal holding whole records.
+type Replayed<K, M, S, V> = (Organizer<K, M, S, V>, u64);
+
+/// An operation of the [`Organizer`] that is recorded in the journal.
+pub(crate) enum Entry<'a, K, M, V: Weight> {
+    /// A node that was taken into the system, with the fork choice override of the call.
+    Insert(&'a ReorgNode<K, M, V>, Option<bool>),
+    /// The nodes of a batch insert, sorted by height, with the fork choice override of the call.
+    Batch(&'a [ReorgNode<K, M, V>], Option<bool>),
+    /// The root moved forward to the node.
+    Root(K),
+    /// The branch stemming from the node was deleted.
+    Delete(K),
+    /// Every node above the height was removed.
+    Rewind(u64),
+    /// A new root was set.
+    Init(&'a ReorgNode<K, M, V>),
+    /// The fork choice rule was changed.
+    ForkChoice(ForkChoice),
+    /// An insert from the source was refused for the reason.
+    Reject(Option<u64>, Rejection),
+    /// The tally of the source was cleared.
+    Forgive(u64),
+}
+
+/// Sink of the journal entries, hiding the codec bounds from the organizer.
+pub(crate) trait Record<K, M, V: Weight> {
+    /// Writes the entry, unless an earlier write has failed.
+    fn record(&mut self, entry: Entry<'_, K, M, V>);
+
+    /// Flushes the sink, or returns the error of the first failed write.
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+/// Journal writing the framed records into a [`Write`] sink.
+struct Journal<W> {
+    writer: W,
+    /// The error of the first failed write, until it is reported.
+    error: Option<io::Error>,
+    /// Set after a write failed, the journal does not record anything anymore.
+    failed: bool,
+}
+
+impl<W: Write> Journal<W> {
+    /// Frames the body with its length and checksums, and writes it.
+    fn write_record(&mut self, body: &[u8]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(body.len() + FRAME);
+        (body.len() as u32).encode(&mut record);
+        crc32(&record).encode(&mut record);
+        record.extend_from_slice(body);
+        crc32(body).encode(&mut record);
+        self.writer.write_all(&record)
+    }
+}
+
+impl<K, M, V, W> Record<K, M, V> for Journal<W>
+where
+    K: Codec + Copy,
+    M: Codec,
+    V: Weight + Codec,
+    V::Sum: Codec,
+    W: Write,
+{
+    fn record(&mut self, entry: Entry<'_, K, M, V>) {
+        if self.failed {
+            return;
+        }
+        let mut body = Vec::new();
+        match entry {
+            Entry::Insert(node, most_valuable) => {
+                body.push(INSERT);
+                body.push(encode_override(most_valuable));
+                encode_node(node, &node.custom_meta, &mut body);
+            }
+            Entry::Batch(nodes, most_valuable) => {
+                body.push(BATCH);
+                body.push(encode_override(most_valuable));
+                (nodes.len() as u64).encode(&mut body);
+                for node in nodes {
+                    encode_node(node, &node.custom_meta, &mut body);
+                }
+            }
+            Entry::Root(key) => {
+                body.push(ROOT);
+                key.encode(&mut body);
+            }
+            Entry::Delete(key) => {
+                body.push(DELETE);
+                key.encode(&mut body);
+            }
+            Entry::Rewind(height) => {
+                body.push(REWIND);
+                height.encode(&mut body);
+            }
+            Entry::Init(node) => {
+                body.push(INIT);
+                encode_node(node, &node.custom_meta, &mut body);
+            }
+            Entry::ForkChoice(fork_choice) => {
+                body.push(FORK_CHOICE);
+                fork_choice.encode(&mut body);
+            }
+            Entry::Reject(source, rejection) => {
+                body.push(REJECT);
+                source.encode(&mut body);
+                rejection.encode(&mut body);
+            }
+            Entry::Forgive(source) => {
+                body.push(FORGIVE);
+                source.encode(&mut body);
+            }
+        }
+        if let Err(error) = self.write_record(&body) {
+            self.error = Some(error);
+            self.failed = true;
+        }
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None if self.failed => Err(io::Error::other(
+                "the journal stopped recording after a failed write",
+            )),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// Encodes the fork choice override of an insert.
+fn encode_override(most_valuable: Option<bool>) -> u8 {
+    match most_valuable {
+        None => 0,
+        Some(false) => 1,
+        Some(true) => 2,
+    }
+}
+
+/// Decodes the fork choice override of an insert.
+fn decode_override(flag: u8) -> Option<Option<bool>> {
+    match flag {
+        0 => Some(None),
+        1 => Some(Some(false)),
+        2 => Some(Some(true)),
+        _ => None,
+    }
+}
+
+/// CRC-32 (IEEE) checksum of the bytes.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+impl<K, M, S, V> Organizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy + Codec,
+    M: Debug + Default + Codec,
+    S: NodeStore<K, M>,
+    V: Weight + Codec,
+    V::Sum: Codec,
+{
+    /// Starts recording every operation into the writer, beginning with a snapshot
+    /// of the current state. A journal that was already started is replaced.
+    /// Appending to the end of an earlier journal is fine, the replay continues from
+    /// the new snapshot.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    /// use std::fs::File;
+    ///
+    /// let path = std::env::temp_dir().join("abandoning_reorg_journal_doc");
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// organizer.start_journal(File::create(&path).unwrap()).unwrap();
+    /// organizer.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    /// organizer.stop_journal().unwrap();
+    /// let (replayed, _) = Organizer::<u64, ()>::replay(File::open(&path).unwrap()).unwrap();
+    /// assert_eq!(replayed.height(), 1);
+    /// ```
+    pub fn start_journal<W>(&mut self, writer: W) -> io::Result<()>
+    where
+        W: Write + Send + Sync + 'static,
+    {
+        let mut journal = Journal {
+            writer,
+            error: None,
+            failed: false,
+        };
+        let mut body = vec![SNAPSHOT];
+        body.extend_from_slice(&self.snapshot());
+        journal.write_record(&body)?;
+        self.journal = Some(Box::new(journal));
+        Ok(())
+    }
+
+    /// Rebuilds an organizer from a journal, with default validators, observers,
+    /// tie breaker and buffer limits. Returns the organizer and the length of the
+    /// journal holding whole records. A torn record at the end of the journal is
+    /// ignored, the journal should be truncated to the returned length before
+    /// appending to it again. A record with a damaged length is refused as corrupted.
+    pub fn replay<R: Read>(reader: R) -> Result<Replayed<K, M, S, V>, JournalError>
+    where
+        S: Default,
+    {
+        OrganizerBuilder::new().replay(reader)
+    }
+}
+
+impl<K, M, S, V> OrganizerBuilder<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy + Codec,
+    M: Debug + Default + Codec,
+    S: NodeStore<K, M>,
+    V: Weight + Codec,
+    V::Sum: Codec,
+{
+    /// Rebuilds an organizer from a journal with the configured validators, observers,
+    /// tie breaker and buffer limits. See [`Organizer::replay`].
+    /// The observers are notified of the replayed operations.
+    pub fn replay<R: Read>(self, mut reader: R) -> Result<Replayed<K, M, S, V>, JournalError> {
+        let mut journal = Vec::new();
+        reader.read_to_end(&mut journal)?;
+        let mut organizer = self.into_organizer();
+        let mut started = false;
+        let mut offset = 0;
+        while journal.len() - offset >= FRAME {
+            let mut input = &journal[offset..];
+            let len = u32::decode(&mut input)? as usize;
+            // A damaged length would pass for a torn record, so it is checked on its own
+            if u32::decode(&mut input)? != crc32(&journal[offset..offset + 4]) {
+                return Err(JournalError::Corrupted(offset as u64));
+            }
+            if input.len() < len + 4 {
+                // The last record was not written completely
+                break;
+            }
+            let (body, mut rest) = input.split_at(len);
+            if u32::decode(&mut rest)? != crc32(body) {
+                if rest.is_empty() {
+                    break;
+                }
+                return Err(JournalError::Corrupted(offset as u64));
+            }
+            let corrupted = || JournalError::Corrupted(offset as u64);
+            let (kind, mut body) = body.split_first().ok_or_else(corrupted)?;
+            if !started && *kind != SNAPSHOT {
+                return Err(JournalError::MissingSnapshot);
+            }
+            match *kind {
+                SNAPSHOT => {
+                    organizer.restore_state(body)?;
+                    started = true;
+                }
+                INSERT => {
+                    let most_valuable =
+                        decode_override(u8::decode(&mut body)?).ok_or_else(corrupted)?;
+                    let node: ReorgNode<K, M, V> = decode_node(&mut body)?;
+                    let arrival = node.arrival;
+                    organizer.insert_from(node, arrival.source, arrival.timestamp, most_valuable);
+                }
+                BATCH => {
+                    let most_valuable =
+                        decode_override(u8::decode(&mut body)?).ok_or_else(corrupted)?;
+                    let mut nodes = Vec::new();
+                    for _ in 0..decode_len(&mut body)? {
+                        nodes.push(decode_node(&mut body)?);
+                    }
+                    organizer.insert_batch(nodes, most_valuable);
+                }
+                ROOT => {
+                    let key = K::decode(&mut body)?;
+                    if key != organizer.root.key {
+                        organizer.finalize(&key);
+                    }
+                }
+                DELETE => {
+                    organizer.delete_children(&K::decode(&mut body)?);
+                }
+                REWIND => {
+                    organizer.rewind_to(u64::decode(&mut body)?);
+                }
+                INIT => organizer.init(decode_node(&mut body)?),
+                FORK_CHOICE => organizer.set_fork_choice(ForkChoice::decode(&mut body)?),
+                REJECT => {
+                    let source = Option::decode(&mut body)?;
+                    organizer.reject(source, Rejection::decode(&mut body)?);
+                }
+                FORGIVE => organizer.forgive(u64::decode(&mut body)?),
+                _ => return Err(corrupted()),
+            }
+            offset += len + FRAME;
+        }
+        if !started {
+            return Err(JournalError::MissingSnapshot);
+        }
+        Ok((organizer, offset as u64))
+    }
+}
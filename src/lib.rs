@@ -5,43 +5,111 @@
 //! all other children are abandoned and removed.
 //! Only dependency is std to try to minimize the dependency hell that
 //! plagues seemingly every project.
+//! Serde support is available behind the optional `serde` feature.
+//! Heights do not have to start at zero or follow each other densely: slot numbers
+//! with skipped slots work as well, as long as every child is higher than its parent.
 
-use std::cmp::Eq;
-use std::collections::HashMap;
+use std::cmp::{Eq, Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 use std::marker::Copy;
 
+mod actor;
+mod builder;
+mod journal;
+mod render;
+#[cfg(feature = "serde")]
+mod serialization;
+mod shared;
+mod snapshot;
+mod store;
+mod weight;
+
+pub use actor::{Actor, ActorHandle, Command, Event, Stopped};
+pub use builder::{BuildError, OrganizerBuilder};
+pub use journal::JournalError;
+pub use shared::{CanonicalChain, SharedOrganizer};
+
+use journal::{Entry, Record};
+pub use snapshot::{Codec, SnapshotError};
+pub use store::{FileStore, MemoryStore, NodeStore};
+pub use weight::Weight;
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Internal node that serves as a "tree node".
-pub struct ReorgNode<K, M> {
+pub struct ReorgNode<K, M, V: Weight = u64> {
     /// key of the node. It is used as its key or name.
     key: K,
-    /// Index of the node in the system.
+    /// Height of the node, for example a block number or a slot. Heights may start
+    /// anywhere and skip, but a node is always higher than its parent.
     height: u64,
-    /// Value of the node.
-    value: u64,
+    /// Value of the node, its weight in the most valuable fork choice.
+    value: V,
     /// key of the node that is parent to this one.
     parent: K,
     /// All nodes that has this node as their "parent",
     children: Vec<K>,
     /// Custom designated meta data
     custom_meta: M,
+    /// When and from where the node arrived, recorded by the organizer.
+    #[cfg_attr(feature = "serde", serde(default))]
+    arrival: Arrival,
+    /// Sum of the values of the node and its ancestors above the first root,
+    /// computed by the organizer.
+    #[cfg_attr(feature = "serde", serde(default = "V::zero"))]
+    cumulative_value: V::Sum,
+    /// Number of nodes from the first root up to this one, computed by the organizer.
+    #[cfg_attr(feature = "serde", serde(default))]
+    cumulative_height: u64,
+}
+
+/// Circumstances of the arrival of a node, recorded by the [`Organizer`] when the node
+/// is taken into the system, apart from the custom meta data of the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arrival {
+    /// The number of nodes taken into the system before this one, plus one.
+    sequence: u64,
+    /// The time of the arrival, as given by the caller.
+    timestamp: Option<u64>,
+    /// The identifier of the source the node came from, as given by the caller.
+    source: Option<u64>,
+}
+
+impl Arrival {
+    /// Getter for the sequence number of the arrival, counted from one. Nodes that
+    /// were not inserted, like the first root, have zero.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Getter for the time of the arrival, in the units the caller chose, for example
+    /// milliseconds since the Unix epoch.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    /// Getter for the identifier of the source the node came from, for example a peer.
+    pub fn source(&self) -> Option<u64> {
+        self.source
+    }
 }
 
-impl<K: Debug, M: Debug> Display for ReorgNode<K, M> {
+impl<K: Debug, M: Debug, V: Weight> Display for ReorgNode<K, M, V> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
             f,
-            ">Key: {:?}\n>Height: {}\n>Value: {}\n>Parent: {:?}\n>Children: {:?}\n>Custom Meta: {:?}",
+            ">Key: {:?}\n>Height: {}\n>Value: {:?}\n>Parent: {:?}\n>Children: {:?}\n>Custom Meta: {:?}",
             self.key, self.height, self.value, self.parent, self.children, self.custom_meta
         )
     }
 }
 
-impl<K, M> ReorgNode<K, M> {
-    pub fn new(key: K, height: u64, value: u64, parent: K, custom_meta: M) -> ReorgNode<K, M> {
+impl<K, M, V: Weight> ReorgNode<K, M, V> {
+    pub fn new(key: K, height: u64, value: V, parent: K, custom_meta: M) -> ReorgNode<K, M, V> {
         ReorgNode {
             key,
             height,
@@ -49,6 +117,9 @@ impl<K, M> ReorgNode<K, M> {
             parent,
             children: Vec::new(),
             custom_meta,
+            arrival: Arrival::default(),
+            cumulative_value: V::zero(),
+            cumulative_height: 0,
         }
     }
 
@@ -60,10 +131,18 @@ impl<K, M> ReorgNode<K, M> {
         self.height
     }
 
-    pub fn value(&self) -> u64 {
+    pub fn value(&self) -> V
+    where
+        V: Copy,
+    {
         self.value
     }
 
+    /// Getter for the value of the node, for weights that can not be copied.
+    pub fn weight(&self) -> &V {
+        &self.value
+    }
+
     pub fn parent(&self) -> &K {
         &self.parent
     }
@@ -75,21 +154,242 @@ impl<K, M> ReorgNode<K, M> {
     pub fn meta(&self) -> &M {
         &self.custom_meta
     }
+
+    /// Getter for the circumstances of the arrival of the node, see [`Arrival`].
+    pub fn arrival(&self) -> &Arrival {
+        &self.arrival
+    }
+
+    /// Getter for the sum of the values of the node and its ancestors above the first
+    /// root of the organizer, which does not change as the root advances.
+    /// See [`Organizer::value_above_root`] for the sum above the current root.
+    pub fn cumulative_value(&self) -> &V::Sum {
+        &self.cumulative_value
+    }
+
+    /// Getter for the number of nodes from the first root of the organizer up to this one.
+    /// See [`Organizer::height_above_root`] for the number above the current root.
+    pub fn cumulative_height(&self) -> u64 {
+        self.cumulative_height
+    }
+
+    /// Splits the node into its topology and its custom meta data.
+    fn into_parts(self) -> (ReorgNode<K, (), V>, M) {
+        let meta = self.custom_meta;
+        let topology = ReorgNode {
+            key: self.key,
+            height: self.height,
+            value: self.value,
+            parent: self.parent,
+            children: self.children,
+            custom_meta: (),
+            arrival: self.arrival,
+            cumulative_value: self.cumulative_value,
+            cumulative_height: self.cumulative_height,
+        };
+        (topology, meta)
+    }
+}
+
+impl<K, V: Weight> ReorgNode<K, (), V> {
+    /// Joins the topology of a node with its custom meta data.
+    fn with_meta<M>(self, custom_meta: M) -> ReorgNode<K, M, V> {
+        ReorgNode {
+            key: self.key,
+            height: self.height,
+            value: self.value,
+            parent: self.parent,
+            children: self.children,
+            custom_meta,
+            arrival: self.arrival,
+            cumulative_value: self.cumulative_value,
+            cumulative_height: self.cumulative_height,
+        }
+    }
 }
 
-impl<K: Default, M: Default> Default for ReorgNode<K, M> {
+impl<K: Default, M: Default, V: Weight> Default for ReorgNode<K, M, V> {
     fn default() -> Self {
-        ReorgNode::new(K::default(), 0, 0, K::default(), M::default())
+        ReorgNode::new(K::default(), 0, V::default(), K::default(), M::default())
+    }
+}
+
+/// Reason for which an inserted node was refused by the [`Organizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rejection {
+    /// A node with the same key is already stored or buffered.
+    Duplicate,
+    /// The node is not younger than the oldest height the organizer still deals with.
+    TooOld,
+    /// The node is not younger than the current root, so it would compete with
+    /// an already finalized node. So would a node right above the root whose parent
+    /// is unknown, like a child of a branch abandoned by [`Organizer::finalize`].
+    Conflicting,
+    /// One of the validators refused the node.
+    Invalid,
+    /// The height of the node is not greater than the height of its parent.
+    NotAboveParent,
+    /// The parent of the node is unknown, and the buffer is already full.
+    BufferFull,
+    /// The parent of the node is unknown, and its height is too far ahead of the
+    /// current height to be buffered.
+    TooFarAhead,
+}
+
+/// Rule by which the [`Organizer`] decides which branch leads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ForkChoice {
+    /// The branch with the longest available lineage leads, counted in nodes.
+    Longest,
+    /// The branch with the greatest accumulated value leads.
+    MostValuable,
+    /// The branch spanning the most heights leads, counted from the child of the root
+    /// up to the head, so that skipped slots count as well. Without gaps between the
+    /// heights this is the same as [`ForkChoice::Longest`].
+    MostSlots,
+}
+
+/// Rule by which the [`Organizer`] decides between equally worthy branches.
+#[derive(Debug, Clone, Copy)]
+pub enum TieBreaker<K> {
+    /// The branch whose head was seen first wins, by the sequence numbers of their
+    /// arrivals, see [`Arrival`].
+    FirstSeen,
+    /// The branch whose head was seen last wins, by the sequence numbers of their
+    /// arrivals.
+    LastSeen,
+    /// The branch for whose key the function returns `Ordering::Greater`
+    /// when compared with the key of the other branch wins.
+    Custom(fn(&K, &K) -> Ordering),
+}
+
+/// Worth of a lineage in the fork choice: its length when the longest branch leads,
+/// the sum of its values when the most valuable one does.
+type Worth<V> = (u64, <V as Weight>::Sum);
+
+/// Function that decides whether a node may be inserted into the [`Organizer`].
+pub type Validator<K, M, V = u64> = Box<dyn Fn(&ReorgNode<K, M, V>) -> bool + Send + Sync>;
+
+/// Callback of [`Organizer::apply_topology_callback`], getting the topology of a node and a
+/// getter reading its custom meta data from the store.
+pub type Callback<'a, K, V, B, T> = dyn FnMut(&ReorgNode<K, (), V>, &dyn Fn() -> B) -> T + 'a;
+
+/// Hook that gets notified about the decisions of the [`Organizer`].
+pub trait Observer<K, M, V: Weight = u64> {
+    /// Called for each node that becomes the root, the oldest first.
+    fn finalized(&mut self, _node: &ReorgNode<K, M, V>) {}
+
+    /// Called for each node that is removed with an abandoned branch.
+    fn abandoned(&mut self, _node: &ReorgNode<K, M, V>) {}
+
+    /// Called when the misbehavior of a source reaches the threshold set by
+    /// [`OrganizerBuilder::misbehavior_threshold`], with the tally of the source.
+    fn misbehaved(&mut self, _source: u64, _misbehavior: &Misbehavior) {}
+}
+
+/// Result of an [`Organizer::insert`] call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InsertOutcome<K> {
+    /// The node was attached to its parent in the tree.
+    Attached {
+        /// The keys of the nodes that became the root as a consequence, the oldest first.
+        finalized: Vec<K>,
+    },
+    /// The parent of the node is not known yet, the node waits for it in the buffer.
+    Buffered,
+    /// The node was discarded.
+    Rejected(Rejection),
+}
+
+/// Aggregated result of an [`Organizer::insert_batch`] call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BatchOutcome<K> {
+    /// The number of nodes attached to the tree, the ones that were waiting in
+    /// the buffer for nodes of the batch included.
+    pub attached: usize,
+    /// The number of nodes of the batch that had to wait in the buffer, some of
+    /// which may have been attached by the end of the batch.
+    pub buffered: usize,
+    /// The keys of the discarded nodes, with the reasons, the buffered ones that turned
+    /// out not to be above their parents included.
+    pub rejected: Vec<(K, Rejection)>,
+    /// The keys of the nodes that became the root at the end of the batch, the oldest first.
+    pub finalized: Vec<K>,
+}
+
+/// Operational statistics of an [`Organizer`], see [`Organizer::stats`].
+/// The counters are kept since the organizer was created or restored. Replaying a
+/// journal counts again every operation recorded after its snapshot, refused inserts included.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// The number of nodes in the tree, the root included.
+    pub nodes: usize,
+    /// The number of nodes waiting in the buffer.
+    pub buffered: usize,
+    /// The number of nodes without children, the heads of every branch.
+    pub tips: usize,
+    /// The number of nodes with more than one child, where the tree forks.
+    pub forks: usize,
+    /// The number of nodes in the longest branch that is not part of the canonical chain.
+    pub max_fork_length: u64,
+    /// The number of times the canonical chain switched to another branch.
+    pub reorgs: u64,
+    /// The greatest number of canonical nodes left behind by a single switch.
+    pub deepest_reorg: u64,
+    /// The number of nodes removed with abandoned or deleted branches.
+    pub pruned: u64,
+    /// The number of buffered nodes that expired before their parents arrived.
+    pub orphans_expired: u64,
+    /// The number of refused inserts by the reason.
+    pub rejections: HashMap<Rejection, u64>,
+}
+
+impl Stats {
+    /// Returns the number of inserts refused for the designated reason.
+    pub fn rejected(&self, reason: Rejection) -> u64 {
+        self.rejections.get(&reason).copied().unwrap_or(0)
+    }
+}
+
+/// Tally of the submissions of a source that were of no use, see [`Organizer::insert_from`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Misbehavior {
+    /// The number of nodes refused by the validators, or not above their parents.
+    pub invalid: u64,
+    /// The number of nodes that were already stored or buffered.
+    pub duplicate: u64,
+    /// The number of nodes older than the allowed depth.
+    pub too_old: u64,
+    /// The number of buffered nodes that expired before their parents arrived.
+    pub expired_orphans: u64,
+}
+
+impl Misbehavior {
+    /// Returns the number of useless submissions of every kind.
+    pub fn total(&self) -> u64 {
+        self.invalid + self.duplicate + self.too_old + self.expired_orphans
     }
 }
 
+/// The number of nodes the buffer holds unless configured otherwise, see
+/// [`OrganizerBuilder::buffer_limit`].
+pub const DEFAULT_BUFFER_LIMIT: usize = 1024;
+
+/// How much greater the height of a buffered node may be than the current height
+/// unless configured otherwise, see [`OrganizerBuilder::max_orphan_distance`].
+pub const DEFAULT_MAX_ORPHAN_DISTANCE: u64 = 1024;
+
 /// Main working struct of the reogranizational code body.
-pub struct Organizer<K, M> {
+/// Only the topology of the nodes is held in memory, their custom meta data
+/// is kept in the [`NodeStore`].
+pub struct Organizer<K, M, S = MemoryStore<K, M>, V: Weight = u64> {
     /// The current root, or oldest node that we deal with.
-    root: ReorgNode<K, M>,
+    root: ReorgNode<K, (), V>,
     /// Every node currently held in the system, stored by their key as its key.
     /// Does not contain the root.
-    nodes_by_key: HashMap<K, ReorgNode<K, M>>,
+    nodes_by_key: HashMap<K, ReorgNode<K, (), V>>,
     /// Every node currently held by the system, stored by their height as the key.
     /// As the main functionality is to decide which branch is the longest, this
     /// map has a Vec as the value field, because multiple nodes with the same
@@ -99,7 +399,9 @@ pub struct Organizer<K, M> {
     /// Buffer for node that doesn't have their parent in the system yet.
     /// This might be because the nodes height is greater by multiple steps
     /// than the one we currently have as head.
-    buffer: HashMap<K, ReorgNode<K, M>>,
+    buffer: HashMap<K, ReorgNode<K, (), V>>,
+    /// The custom meta data of the root, and every stored and buffered node.
+    store: S,
     /// The height of the node with currently greatest height in the system.
     /// (Can also be described as the youngest or newest nodes height.)
     /// (Does not include nodes in the buffer)
@@ -108,28 +410,60 @@ pub struct Organizer<K, M> {
     /// are discarded.
     allowed_depth: u64,
     /// Sets the Organizer to search for the "most valuable" branches instead
-    /// of the longest ones. Accumulates the value fields of the nodes.
-    value_based: bool,
+    /// of the longest ones, which accumulates the value fields of the nodes.
+    fork_choice: ForkChoice,
+    /// Decides between branches of equal worth.
+    tie_breaker: TieBreaker<K>,
+    /// The greatest number of nodes the buffer may hold.
+    buffer_limit: usize,
+    /// How much greater the height of a buffered node may be than the current height.
+    max_orphan_distance: u64,
+    /// The total of useless submissions at which a source is reported to the observers,
+    /// if set.
+    misbehavior_threshold: Option<u64>,
+    /// The tally of useless submissions by their sources.
+    misbehavior: HashMap<u64, Misbehavior>,
+    /// Every inserted node has to pass all of these to be taken into the system.
+    validators: Vec<Validator<K, M, V>>,
+    /// Get notified about every finalized and abandoned node.
+    observers: Vec<Box<dyn Observer<K, M, V> + Send + Sync>>,
+    /// Records every operation, if a journal was started.
+    journal: Option<Box<dyn Record<K, M, V> + Send + Sync>>,
+    /// The counters of the statistics, the ones computed from the tree left at zero.
+    counters: Stats,
+    /// The number of nodes taken into the system so far, numbering their arrivals.
+    arrivals: u64,
+    /// The canonical head after the last insert, used to detect reorganizations.
+    /// Cleared when the canonical head could have changed otherwise.
+    head: Option<K>,
+    /// Sets the Organizer to hold multiple candidate roots under a placeholder root,
+    /// until the root advances past the placeholder or a node is finalized.
+    forest: bool,
+    /// The original parents of the candidate roots in the forest, stored by the
+    /// key of the candidate. Candidate roots have the placeholder as their parent
+    /// until one of their ancestors is inserted and they converge into its branch.
+    forest_parents: HashMap<K, K>,
+    /// Lets every tip of the tree compete for the lead, instead of the nodes at the
+    /// greatest height only, for heights that are slots some of which are skipped.
+    slot_based: bool,
 }
 
-impl<K: Debug, M: Debug> Display for Organizer<K, M> {
+impl<K: Debug, M, S, V: Weight> Display for Organizer<K, M, S, V> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "Root: \n{}\nNode Key Count: {}\nNode Height Count: {}\nHeight: {:?}\nAllowed Depth: {:?}", 
         self.root, self.nodes_by_key.len(), self.nodes_by_height.len(), self.height, self.allowed_depth)
     }
 }
 
-impl<K: Default, M: Default> Default for Organizer<K, M> {
+impl<K, M, S, V> Default for Organizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy,
+    M: Debug + Default,
+    S: NodeStore<K, M> + Default,
+    V: Weight,
+{
     fn default() -> Self {
-        Organizer {
-            height: 0,
-            root: ReorgNode::default(),
-            nodes_by_key: HashMap::new(),
-            nodes_by_height: HashMap::new(),
-            buffer: HashMap::new(),
-            allowed_depth: 255,
-            value_based: false,
-        }
+        Organizer::with_store(S::default(), 255)
     }
 }
 
@@ -139,18 +473,12 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
     /// ```
     /// use abandoning_reorg::Organizer;
     ///
-    /// abandoning_reorg::Organizer::new(777);
+    /// Organizer::<[u8; 32], ()>::new(777, false);
     /// ```
     pub fn new(allowed_depth: u64, value_based: bool) -> Organizer<K, M> {
-        Self {
-            height: 0,
-            root: ReorgNode::default(),
-            nodes_by_key: HashMap::new(),
-            nodes_by_height: HashMap::new(),
-            buffer: HashMap::new(),
-            allowed_depth,
-            value_based,
-        }
+        let mut organizer = Self::with_store(MemoryStore::default(), allowed_depth);
+        organizer.set_value_based(value_based);
+        organizer
     }
 
     /// Constructor function that takes the first root node - possibly the genesis node -
@@ -162,26 +490,110 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
     /// use abandoning_reorg::ReorgNode;
     ///
     /// let initial_node = ReorgNode::default();
-    /// Organizer::<[u8; 32], ()>::new_with(initial_node, 777);
+    /// Organizer::<[u8; 32], ()>::new_with(initial_node, 777, false);
     /// ```
     pub fn new_with(
         root: ReorgNode<K, M>,
         allowed_depth: u64,
         value_based: bool,
     ) -> Organizer<K, M> {
+        let mut organizer = Self::new(allowed_depth, value_based);
+        organizer.init(root);
+        organizer
+    }
+
+    /// Returns a builder to configure every aspect of an Organizer in one place.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{ForkChoice, Organizer, ReorgNode};
+    ///
+    /// let organizer = Organizer::<[u8; 32], ()>::builder()
+    ///     .depth(777)
+    ///     .fork_choice(ForkChoice::MostValuable)
+    ///     .root(ReorgNode::default())
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(organizer.fork_choice(), ForkChoice::MostValuable);
+    /// ```
+    pub fn builder() -> OrganizerBuilder<K, M> {
+        OrganizerBuilder::new()
+    }
+
+    /// Constructor for the forest mode, in which there is no known common root at startup.
+    /// The root is a placeholder node with the default key, and every inserted node whose
+    /// parent is unknown becomes a candidate root under it, instead of going into the buffer.
+    /// Candidate roots converge when one of their ancestors is inserted, and the forest
+    /// collapses into a single tree once the root advances and the best candidate wins,
+    /// or when a node is finalized.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::Organizer;
+    ///
+    /// let organizer = Organizer::<[u8; 32], ()>::new_forest(777, false);
+    /// assert!(organizer.is_forest());
+    /// ```
+    pub fn new_forest(allowed_depth: u64, value_based: bool) -> Organizer<K, M> {
+        let mut organizer = Self::new(allowed_depth, value_based);
+        organizer.start_forest();
+        organizer
+    }
+}
+
+impl<K, M, S, V> Organizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy,
+    M: Debug + Default,
+    S: NodeStore<K, M>,
+    V: Weight,
+{
+    /// Constructor that keeps the custom meta data of the nodes in the designated store.
+    /// The root is a default node, see [`Organizer::init`]. At most [`DEFAULT_BUFFER_LIMIT`]
+    /// orphans are buffered, up to [`DEFAULT_MAX_ORPHAN_DISTANCE`] above the current height.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{MemoryStore, Organizer, ReorgNode};
+    ///
+    /// let mut organizer: Organizer<u64, String> = Organizer::with_store(MemoryStore::default(), 777);
+    /// organizer.init(ReorgNode::new(1, 0, 0, 0, "genesis".to_string()));
+    /// assert_eq!(organizer.meta(&1).unwrap(), "genesis");
+    /// ```
+    pub fn with_store(mut store: S, allowed_depth: u64) -> Organizer<K, M, S, V> {
+        let (root, meta) = ReorgNode::default().into_parts();
+        store.put(root.key, meta);
+        // The default root is indexed like any other root, so that it can lead the tree
         let mut nodes_by_height = HashMap::new();
         nodes_by_height.insert(root.height, vec![root.key]);
-        Self {
-            height: root.height,
+        Organizer {
+            height: 0,
             root,
             nodes_by_key: HashMap::new(),
             nodes_by_height,
             buffer: HashMap::new(),
+            store,
             allowed_depth,
-            value_based,
+            fork_choice: ForkChoice::Longest,
+            tie_breaker: TieBreaker::FirstSeen,
+            buffer_limit: DEFAULT_BUFFER_LIMIT,
+            max_orphan_distance: DEFAULT_MAX_ORPHAN_DISTANCE,
+            misbehavior_threshold: None,
+            misbehavior: HashMap::new(),
+            validators: Vec::new(),
+            observers: Vec::new(),
+            journal: None,
+            forest: false,
+            forest_parents: HashMap::new(),
+            slot_based: false,
+            counters: Stats::default(),
+            arrivals: 0,
+            head: None,
         }
     }
 
+    /// Switches to the forest mode, the current root becoming the placeholder.
+    pub(crate) fn start_forest(&mut self) {
+        self.forest = true;
+    }
+
     /// Init function, sets a new root.
     /// Examples
     /// ```
@@ -189,18 +601,29 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
     /// use abandoning_reorg::ReorgNode;
     ///
     /// let initial_node = ReorgNode::default();
-    /// let organizer = Organizer::default;
+    /// let mut organizer = Organizer::<[u8; 32], ()>::default();
     /// organizer.init(initial_node);
     /// ```
-    pub fn init(&mut self, first_root: ReorgNode<K, M>) {
+    pub fn init(&mut self, first_root: ReorgNode<K, M, V>) {
+        self.record(Entry::Init(&first_root));
+        self.leave_forest();
+        self.head = None;
+        self.store.remove(&self.root.key);
+        let mut first_root = self.keep_body(first_root);
+        // The cumulative values are counted from the first root
+        first_root.cumulative_value = V::zero();
+        first_root.cumulative_height = 0;
         self.height = first_root.height;
-        self.nodes_by_height
-            .insert(first_root.height, vec![first_root.key]);
+        let old_key = self.root.key;
+        self.unindex_height(self.root.height, &old_key);
+        self.index_height(first_root.height, first_root.key);
         self.root = first_root;
     }
 
     /// Returns the difference of height and the allowed depth to determine the highest node
-    /// that we allow, or zero if the number would be negative.
+    /// that we allow, or zero if the number would be negative. In slot based mode the
+    /// height of the canonical head is taken, so that a node far ahead on a losing branch
+    /// does not force the root forward.
     ///
     /// Examples
     /// ```
@@ -208,31 +631,67 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
     /// use crate::abandoning_reorg::ReorgNode;
     ///
     /// let initial_node = ReorgNode::default();
-    /// let organizer = Organizer::<[u8; 32], ()>::new_with(initial_node, 777);
-    /// assert_eq!(organizer.allowed_oldest(),777);
+    /// let organizer = Organizer::<[u8; 32], ()>::new_with(initial_node, 777, false);
+    /// assert_eq!(organizer.allowed_oldest(), 0);
     ///
     /// let organizer = Organizer::<[u8; 32], ()>::default();
-    /// assert_eq!(organizer.allowed_oldest(), 255);
+    /// assert_eq!(organizer.allowed_oldest(), 0);
     /// ```
     pub fn allowed_oldest(&self) -> u64 {
-        self.height.saturating_sub(self.allowed_depth as u64)
+        let height = if self.slot_based {
+            let head = self.head.unwrap_or_else(|| self.canonical_head());
+            self.get_topology(&head)
+                .map_or(self.height, |head| head.height)
+        } else {
+            self.height
+        };
+        height.saturating_sub(self.allowed_depth)
     }
 
     /// Switches the Organizer to and from value searching mode.
     pub fn set_value_based(&mut self, switch: bool) {
-        self.value_based = switch;
+        self.set_fork_choice(if switch {
+            ForkChoice::MostValuable
+        } else {
+            ForkChoice::Longest
+        });
+    }
+
+    /// Sets the rule by which the leading branch is decided.
+    pub fn set_fork_choice(&mut self, fork_choice: ForkChoice) {
+        self.record(Entry::ForkChoice(fork_choice));
+        self.fork_choice = fork_choice;
+        self.head = None;
+    }
+
+    /// Getter for the rule by which the leading branch is decided.
+    pub fn fork_choice(&self) -> ForkChoice {
+        self.fork_choice
+    }
+
+    /// Adds an observer that is notified about every finalized and abandoned node.
+    pub fn add_observer<O>(&mut self, observer: O)
+    where
+        O: Observer<K, M, V> + Send + Sync + 'static,
+    {
+        self.observers.push(Box::new(observer));
     }
 
     /// This function is part of the garbage collection. Deletes every node that in the branch
     /// stemming from the node we designated.
-    pub fn delete_children(&mut self, branch_root: &K) -> Vec<ReorgNode<K, M>> {
-        let mut ret: Vec<ReorgNode<K, M>> = Vec::new();
+    pub fn delete_children(&mut self, branch_root: &K) -> Vec<ReorgNode<K, M, V>> {
+        let mut ret: Vec<ReorgNode<K, M, V>> = Vec::new();
         // First we try to remove the designated node from the system
         if let Some(removed) = self.nodes_by_key.remove(branch_root) {
+            self.record(Entry::Delete(*branch_root));
+            self.head = None;
+            // The parent should no longer list the removed node as its child
+            self.unlink_child(&removed.parent, branch_root);
+            self.unindex_height(removed.height, branch_root);
             // We add the removed nodes children to the list that we will remove next
             let mut removeable: Vec<K> = removed.children.clone();
             // We push the node into the list of nodes we will return
-            ret.push(removed);
+            ret.push(self.take_body(removed));
             // As long as there are possible nodes in this branch we repeatedly
             // remove a node, if it succeeds we push its children to
             // the list of removable nodes, then append the node to the return list.
@@ -240,28 +699,113 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
                 let mut remove_next = Vec::new();
                 for key in &removeable {
                     if let Some(mut removed_last) = self.nodes_by_key.remove(key) {
+                        self.unindex_height(removed_last.height, key);
                         remove_next.append(&mut removed_last.children);
-                        ret.push(removed_last);
+                        ret.push(self.take_body(removed_last));
                     }
                 }
                 removeable = remove_next;
             }
+            self.counters.pruned += ret.len() as u64;
+            self.recompute_height();
         }
         ret
     }
 
-    /// Utility function that lists node stored by their keyes. (Only prints the keyes)
-    pub fn list_node_keyes(&self) {
-        for key in self.nodes_by_key.keys() {
-            println!("{:?}", key)
+    /// Forces the root forward to the designated node, as if it was finalized by
+    /// an outside authority. Every branch that does not descend from the node is
+    /// abandoned, every node older than it is dropped, as well as the buffered
+    /// nodes that could only ever attach below it. From then on inserts that would
+    /// compete with the finalized node are rejected as [`Rejection::Conflicting`].
+    /// Returns the abandoned nodes, or `None` if the node is not part of the tree.
+    pub fn finalize(&mut self, key: &K) -> Option<Vec<ReorgNode<K, M, V>>> {
+        if *key == self.root.key {
+            return Some(Vec::new());
+        }
+        // We collect the lineage of the node up to the current root, the finalized
+        // node being the first and the roots immidiate child being the last.
+        let mut lineage = vec![*key];
+        let mut cursor = self.nodes_by_key.get(key)?.parent;
+        while cursor != self.root.key {
+            lineage.push(cursor);
+            cursor = self
+                .nodes_by_key
+                .get(&cursor)
+                .expect("a node in the tree has a parent that is not stored")
+                .parent;
+        }
+        let mut abandoned = Vec::new();
+        self.head = None;
+        // Walking down the lineage, every sibling branch of the next ancestor dies.
+        let mut siblings = self.root.children.clone();
+        while let Some(next) = lineage.pop() {
+            for dead_branch in siblings {
+                if dead_branch != next {
+                    abandoned.append(&mut self.delete_children(&dead_branch));
+                }
+            }
+            let ancestor = self
+                .nodes_by_key
+                .remove(&next)
+                .expect("a node in the lineage was removed while finalizing");
+            siblings = ancestor.children.clone();
+            // The last ancestor is the finalized node itself, which stays indexed by height.
+            if lineage.is_empty() {
+                let old_root = std::mem::replace(&mut self.root, ancestor);
+                self.store.remove(&old_root.key);
+                self.leave_forest();
+                self.notify_finalized(None);
+            } else {
+                self.unindex_height(ancestor.height, &next);
+                let ancestor = self.take_body(ancestor);
+                self.notify_finalized(Some(&ancestor));
+            }
         }
+        self.notify_abandoned(&abandoned);
+        let root_height = self.root.height;
+        self.nodes_by_height
+            .retain(|height, _| *height >= root_height);
+        self.expire_buffer(root_height.saturating_add(1));
+        self.record(Entry::Root(self.root.key));
+        Some(abandoned)
     }
 
-    /// Utility that prints the node stored by their keyes. (Actually displays the nodes)
-    pub fn list_nodes(&self) {
-        for node in self.nodes_by_key.values() {
-            println!("{}\n", node)
+    /// Removes every node above the designated height from the tree, and sets the
+    /// height of the system accordingly. The root is never removed, and the buffer
+    /// is left intact so that the removed nodes can be inserted again.
+    /// Returns the removed nodes, the highest ones first.
+    pub fn rewind_to(&mut self, height: u64) -> Vec<ReorgNode<K, M, V>> {
+        self.record(Entry::Rewind(height));
+        self.head = None;
+        let floor = height.max(self.root.height);
+        let mut heights: Vec<u64> = self
+            .nodes_by_height
+            .keys()
+            .copied()
+            .filter(|h| *h > floor)
+            .collect();
+        heights.sort_unstable_by(|a, b| b.cmp(a));
+        let mut ret = Vec::new();
+        for h in heights {
+            if let Some(keys) = self.nodes_by_height.remove(&h) {
+                for key in keys {
+                    if let Some(mut node) = self.nodes_by_key.remove(&key) {
+                        self.unlink_child(&node.parent, &key);
+                        // Every child is above the node, so they are removed as well
+                        node.children.clear();
+                        ret.push(self.take_body(node));
+                    }
+                }
+            }
         }
+        self.recompute_height();
+        ret
+    }
+
+    /// Removes every node at the current greatest height, rewinding the system by one.
+    /// Returns the removed nodes, which is empty if only the root is left.
+    pub fn pop_head(&mut self) -> Vec<ReorgNode<K, M, V>> {
+        self.rewind_to(self.height.saturating_sub(1))
     }
 
     /// Returns the key of the node that is the immidiate child of the current root,
@@ -269,56 +813,178 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
     /// # Panics
     /// If this function call fails that means that at least one node was not stored in the memory.
     pub fn find_longest_branch(&self, most_valuable: Option<bool>) -> K {
-        // We take the nodes that correspond to the greatest available
-        // height stored in the system as the heads of the tree.
-        // This should not fail for we always store every node by their height.
-        let heads = self
-            .nodes_by_height
-            .get(&self.height)
-            .expect("there in no node stored corresponding to the greatest logged height");
-        let mut lead_branches: HashMap<K, u64> = HashMap::new();
+        let mut lead_branches: Vec<(K, Worth<V>, u64)> = Vec::new();
         // We check each head of the tree
-        for head in heads {
-            let mut worth = 0;
-            let mut root = head;
-            // We count the lineage number of each branch from head to root
-            while let Some(node) = self.nodes_by_key.get(root) {
-                if node.parent != self.root.key {
-                    root = &node.parent;
-                    worth += if most_valuable.unwrap_or(self.value_based) {
-                        node.value
-                    } else {
-                        1
-                    };
-                } else {
-                    // When we reached the roots immidiate child we break out of the loop
-                    break;
+        for head in &self.heads() {
+            let (root, worth) = self.head_worth(head, most_valuable);
+            let arrived = self.arrival(head).map_or(0, |arrival| arrival.sequence);
+            // Save the height of the branch with the branches root (the system roots child)
+            // as the key, keeping the worthiest head if the branch has more, the first
+            // arrived one of equally worthy heads.
+            match lead_branches.iter_mut().find(|(key, _, _)| *key == root) {
+                Some((_, greatest, first)) => {
+                    if (&worth, Reverse(arrived)) > (&*greatest, Reverse(*first)) {
+                        *greatest = worth;
+                        *first = arrived;
+                    }
                 }
+                None => lead_branches.push((root, worth, arrived)),
             }
-            // Insert the height of the branch with the branches root (the system roots child)
-            // as the key.
-            lead_branches.insert(*root, worth);
         }
-        let (mut most_valuable_key, mut greatest_worth) = (K::default(), 0);
         // After the parsed every branch corresponding to a head, we determine the longest and return
-        // its key
-        for (key, worth) in lead_branches {
-            if worth > greatest_worth {
-                greatest_worth = worth;
-                most_valuable_key = key;
+        // its key. On a tie the tie breaker decides, by the arrivals of the heads.
+        let mut best: Option<(K, Worth<V>, u64)> = None;
+        for (key, worth, arrived) in lead_branches {
+            let wins = match &best {
+                None => true,
+                Some((best_key, greatest_worth, best_arrived)) => match worth.cmp(greatest_worth) {
+                    Ordering::Greater => true,
+                    Ordering::Less => false,
+                    Ordering::Equal => match self.tie_breaker {
+                        TieBreaker::FirstSeen => arrived < *best_arrived,
+                        TieBreaker::LastSeen => arrived > *best_arrived,
+                        TieBreaker::Custom(compare) => compare(&key, best_key) == Ordering::Greater,
+                    },
+                },
+            };
+            if wins {
+                best = Some((key, worth, arrived));
+            }
+        }
+        best.map_or(self.root.key, |(key, _, _)| key)
+    }
+
+    /// Returns the keys of the nodes competing for the lead: the nodes at the greatest
+    /// height, or every tip of the tree in slot based mode, or the root if it is alone.
+    /// # Panics
+    /// If no node is stored at the greatest height, which means that the index of the
+    /// heights is broken.
+    fn heads(&self) -> Vec<K> {
+        if !self.slot_based {
+            // This should not fail for we always store every node by their height.
+            return self
+                .nodes_by_height
+                .get(&self.height)
+                .expect("there in no node stored corresponding to the greatest logged height")
+                .clone();
+        }
+        let tips: Vec<K> = self
+            .nodes_by_key
+            .values()
+            .filter(|node| node.children.is_empty())
+            .map(|node| node.key)
+            .collect();
+        if tips.is_empty() {
+            vec![self.root.key]
+        } else {
+            tips
+        }
+    }
+
+    /// Counts the worth of the lineage from the designated head down to the roots
+    /// immidiate child, the child not included, returning the key of that child and
+    /// the worth.
+    fn head_worth(&self, head: &K, most_valuable: Option<bool>) -> (K, Worth<V>) {
+        let value_based = most_valuable.unwrap_or(self.fork_choice == ForkChoice::MostValuable);
+        let mut root = head;
+        // We follow the lineage of each branch from head to root
+        while let Some(node) = self.nodes_by_key.get(root) {
+            if node.parent != self.root.key {
+                root = &node.parent;
+            } else {
+                // When we reached the roots immidiate child we break out of the loop
+                break;
             }
         }
-        most_valuable_key
+        // The worth is the difference of the cumulative values, the heights, or the
+        // cumulative heights of the head and the child
+        let worth = match (self.nodes_by_key.get(head), self.nodes_by_key.get(root)) {
+            (Some(head), Some(child)) => {
+                if value_based {
+                    (0, V::sub(&head.cumulative_value, &child.cumulative_value))
+                } else if self.fork_choice == ForkChoice::MostSlots {
+                    (head.height - child.height, V::zero())
+                } else {
+                    (head.cumulative_height - child.cumulative_height, V::zero())
+                }
+            }
+            _ => (0, V::zero()),
+        };
+        (*root, worth)
+    }
+
+    /// Returns the key of the head of the canonical chain: the worthiest node at the
+    /// greatest height in the branch the root would advance into, the first arrived
+    /// one of equally worthy nodes, or the root itself if it has no children.
+    pub fn canonical_head(&self) -> K {
+        self.lead_head(None)
+    }
+
+    /// Returns the worthiest head in the branch the root would advance into,
+    /// see [`Organizer::canonical_head`].
+    fn lead_head(&self, most_valuable: Option<bool>) -> K {
+        let branch = self.find_longest_branch(most_valuable);
+        if branch == self.root.key {
+            return branch;
+        }
+        let mut best: Option<(K, Worth<V>, u64)> = None;
+        for head in &self.heads() {
+            let (root, worth) = self.head_worth(head, most_valuable);
+            let arrived = self.arrival(head).map_or(0, |arrival| arrival.sequence);
+            if root != branch {
+                continue;
+            }
+            let wins = match &best {
+                None => true,
+                Some((_, greatest, first)) => {
+                    (&worth, Reverse(arrived)) > (greatest, Reverse(*first))
+                }
+            };
+            if wins {
+                best = Some((*head, worth, arrived));
+            }
+        }
+        best.map_or(branch, |(head, _, _)| head)
+    }
+
+    /// Returns the keys of the canonical chain from the root up to the canonical head.
+    /// See [`Organizer::canonical_head`].
+    pub fn canonical_chain(&self) -> Vec<K> {
+        let mut chain = vec![self.canonical_head()];
+        while let Some(node) = self.nodes_by_key.get(&chain[chain.len() - 1]) {
+            chain.push(node.parent);
+        }
+        chain.reverse();
+        chain
     }
 
     /// Apply callback from given head to given root, or as long as possible.
     /// If no head is supplied try to go from the highest, but only if
     /// there is only one node at the greatest height,
+    /// Nothing is called back for a head that is not stored.
+    /// The nodes are called back with their custom meta data read from the store, see
+    /// [`Organizer::apply_topology_callback`] to read it only when it is needed.
     pub fn apply_callback<T>(
         &self,
         head: Option<K>,
         root: Option<K>,
-        callback: &mut dyn FnMut(&ReorgNode<K, M>) -> T,
+        callback: &mut dyn FnMut(&ReorgNode<K, M, V>) -> T,
+    ) where
+        M: Clone,
+    {
+        self.apply_topology_callback(head, root, &mut |node, meta| {
+            callback(&node.clone().with_meta(M::clone(&meta())))
+        });
+    }
+
+    /// Applies the callback as [`Organizer::apply_callback`] does, but hands it the
+    /// topology of the node and a getter reading its custom meta data from the store,
+    /// so that the meta data is only read when it is needed.
+    pub fn apply_topology_callback<'s, T>(
+        &'s self,
+        head: Option<K>,
+        root: Option<K>,
+        callback: &mut Callback<'_, K, V, S::Body<'s>, T>,
     ) {
         let head = match head {
             Some(head) => head,
@@ -333,11 +999,11 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
                 None => return,
             },
         };
-        let head_node = self
-            .nodes_by_key
-            .get(&head)
-            .expect("there in no node stored corresponding to the gived key");
-        callback(head_node);
+        let head_node = match self.nodes_by_key.get(&head) {
+            Some(node) => node,
+            None => return,
+        };
+        callback(head_node, &|| self.body(&head_node.key));
         let mut cursor = head_node.parent;
         while let Some(node) = self.nodes_by_key.get(&cursor) {
             match root {
@@ -352,7 +1018,7 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
                     cursor = node.parent;
                 }
             }
-            callback(node);
+            callback(node, &|| self.body(&node.key));
         }
     }
 
@@ -378,48 +1044,215 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
     /// or into the buffer if parent is not present but has a good height.
     /// Otherwise the node is discarded.
     /// The height of the node is considered good if its greater than that of the current root.
+    /// Orphans are buffered whatever the current height is, so that a side branch delivered
+    /// child first is not lost, and the tree does not depend on the order of delivery
+    /// within the allowed depth.
+    /// Returns whether the node was attached, buffered or rejected. When the node
+    /// is attached, the root is moved forward as far as the allowed depth requires,
+    /// and the keys of the nodes that became the root are returned.
     /// Panics
     /// A panic will occur if a node has a child listed that we do not have
     /// stored by its key.
-    pub fn insert(&mut self, node: ReorgNode<K, M>, most_valuable: Option<bool>) {
-        // if new node older than we search, we don't care about it
-        if node.height <= self.allowed_oldest() {
-            return;
+    pub fn insert(
+        &mut self,
+        node: ReorgNode<K, M, V>,
+        most_valuable: Option<bool>,
+    ) -> InsertOutcome<K> {
+        self.insert_from(node, None, None, most_valuable)
+    }
+
+    /// Inserts the node as [`Organizer::insert`] does, recording the source it came
+    /// from and the time it arrived at with the sequence number of its arrival.
+    /// See [`Arrival`]. The invalid, duplicate and too old nodes of the source, and
+    /// its buffered nodes that expire are tallied, see [`Organizer::misbehavior`].
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// organizer.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    /// organizer.insert_from(ReorgNode::new(3, 1, 0, 1, ()), Some(7), Some(1_700_000_000), None);
+    /// let arrival = organizer.arrival(&3).unwrap();
+    /// assert_eq!(arrival.sequence(), 2);
+    /// assert_eq!(arrival.source(), Some(7));
+    /// assert_eq!(arrival.timestamp(), Some(1_700_000_000));
+    /// ```
+    pub fn insert_from(
+        &mut self,
+        mut node: ReorgNode<K, M, V>,
+        source: Option<u64>,
+        timestamp: Option<u64>,
+        most_valuable: Option<bool>,
+    ) -> InsertOutcome<K> {
+        if let Err(rejection) = self.admit(&node) {
+            self.reject(source, rejection);
+            return InsertOutcome::Rejected(rejection);
         }
-        // if new nodes parent isn't stored already and it's height isn't greater than
-        // what we know the newest to be, we don't care about it
-        if !self.nodes_by_key.contains_key(&node.parent) && node.height <= self.height {
-            return;
+        node.arrival = self.arrive(source, timestamp);
+        self.record(Entry::Insert(&node, most_valuable));
+        let old_head = self.current_head();
+        let key = node.key;
+        if !self.place(node) {
+            return InsertOutcome::Buffered;
         }
-        // when the root nodes depth reaches the threshold we predetermined
-        if self.root.height == self.allowed_oldest() {
-            match self.root.children.len() {
-                0 => {}
-                1 => {
-                    // In case the root has only one child, the child becomes the new node.
-                    // If this fails that means the children of the root were already removed.
-                    self.root = self.nodes_by_key.remove(&self.root.children[0]).unwrap();
+        // Nodes in the buffer might have been waiting for this one
+        let (attached, _) = self.resolve_buffer();
+        self.track_head(old_head, attached.is_empty().then_some(key));
+        // When the root nodes depth passes the threshold we predetermined, it is moved forward
+        let finalized = self.advance_root(most_valuable);
+        // We check the nodes in the buffer wether they have expired
+        self.expire_buffer(self.allowed_oldest());
+        InsertOutcome::Attached { finalized }
+    }
+
+    /// Inserts many nodes at once, for example during the initial sync. The nodes are
+    /// sorted by their height, numbered and attached in that order, then the buffer is
+    /// resolved. The root is moved forward down the lineage of the worthiest head, each
+    /// time the tree grew twice as deep as allowed, and at the end. As the fork choice is
+    /// evaluated that rarely, the root may advance into another branch than it would with
+    /// the nodes inserted one by one.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 10, false);
+    /// let nodes = (1..100).rev().map(|i| ReorgNode::new(i, i, 0, i - 1, ()));
+    /// let outcome = organizer.insert_batch(nodes, None);
+    /// assert_eq!(outcome.attached, 99);
+    /// assert_eq!(organizer.root().key(), &89);
+    /// ```
+    pub fn insert_batch<I>(&mut self, nodes: I, most_valuable: Option<bool>) -> BatchOutcome<K>
+    where
+        I: IntoIterator<Item = ReorgNode<K, M, V>>,
+    {
+        let mut nodes: Vec<ReorgNode<K, M, V>> = nodes.into_iter().collect();
+        nodes.sort_by_key(|node| node.height);
+        self.record(Entry::Batch(&nodes, most_valuable));
+        let old_head = self.current_head();
+        let mut outcome = BatchOutcome {
+            attached: 0,
+            buffered: 0,
+            rejected: Vec::new(),
+            finalized: Vec::new(),
+        };
+        for mut node in nodes {
+            match self.admit(&node) {
+                Err(rejection) => {
+                    *self.counters.rejections.entry(rejection).or_insert(0) += 1;
+                    outcome.rejected.push((node.key, rejection));
                 }
-                _ => {
-                    // In case the root has multiple children we determine the longest branch.
-                    let remove = self.root.children.clone();
-                    // We replace the current root with its child that heirs the longest lineage.
-                    // If this fails that means that the branch has already been removed.
-                    self.root =
-                        self.nodes_by_key
-                            .remove(&self.find_longest_branch(Some(
-                                most_valuable.unwrap_or(self.value_based),
-                            )))
-                            .unwrap();
-                    for dead_branch in remove {
-                        // we delete every branch stemming from the root other than the longest one
-                        if dead_branch != self.root.key {
-                            self.delete_children(&dead_branch);
+                Ok(()) => {
+                    node.arrival = self.arrive(None, None);
+                    if self.place(node) {
+                        outcome.attached += 1;
+                        // The tree is pruned whenever it grew twice as deep as allowed
+                        if self.root.height.saturating_add(self.allowed_depth)
+                            < self.allowed_oldest()
+                        {
+                            let lead = self.lead_head(most_valuable);
+                            let mut finalized = self.advance_to_lead(lead, most_valuable);
+                            outcome.finalized.append(&mut finalized);
                         }
+                    } else {
+                        outcome.buffered += 1;
                     }
                 }
             }
         }
+        let (attached, dropped) = self.resolve_buffer();
+        outcome.attached += attached.len();
+        outcome.rejected.extend(
+            dropped
+                .into_iter()
+                .map(|key| (key, Rejection::NotAboveParent)),
+        );
+        self.track_head(old_head, None);
+        let lead = match most_valuable {
+            None => self.current_head(),
+            Some(_) => self.lead_head(most_valuable),
+        };
+        let mut finalized = self.advance_to_lead(lead, most_valuable);
+        outcome.finalized.append(&mut finalized);
+        self.expire_buffer(self.allowed_oldest());
+        outcome
+    }
+
+    /// Numbers the arrival of a node that is taken into the system.
+    fn arrive(&mut self, source: Option<u64>, timestamp: Option<u64>) -> Arrival {
+        self.arrivals += 1;
+        Arrival {
+            sequence: self.arrivals,
+            timestamp,
+            source,
+        }
+    }
+
+    /// Decides whether the node may be taken into the system, checking its height,
+    /// its parent, the validators and the limits of the buffer.
+    fn admit(&mut self, node: &ReorgNode<K, M, V>) -> Result<(), Rejection> {
+        if node.key == self.root.key
+            || self.nodes_by_key.contains_key(&node.key)
+            || self.buffer.contains_key(&node.key)
+        {
+            return Err(Rejection::Duplicate);
+        }
+        // if new node older than we search, we don't care about it
+        if node.height <= self.allowed_oldest() {
+            return Err(Rejection::TooOld);
+        }
+        // in the forest the placeholder root is kept right below the lowest candidate root,
+        // it is only moved there once the node has passed every check
+        let lower_placeholder = self.forest
+            && !self.nodes_by_key.contains_key(&node.parent)
+            && (self.root.children.is_empty() || node.height <= self.root.height);
+        let root_height = if lower_placeholder {
+            node.height.saturating_sub(1)
+        } else {
+            self.root.height
+        };
+        // if the root has been forced forward, nothing can be inserted next to or below it
+        if node.height <= root_height {
+            return Err(Rejection::Conflicting);
+        }
+        // heights may skip, but they have to grow from the parent to the child
+        let parent_height = if node.parent == self.root.key {
+            Some(root_height)
+        } else {
+            self.nodes_by_key
+                .get(&node.parent)
+                .map(|parent| parent.height)
+        };
+        if parent_height.is_some_and(|height| node.height <= height) {
+            return Err(Rejection::NotAboveParent);
+        }
+        if !self.validators.iter().all(|validator| validator(node)) {
+            return Err(Rejection::Invalid);
+        }
+        let parent_known =
+            self.nodes_by_key.contains_key(&node.parent) || node.parent == self.root.key;
+        if !parent_known && !self.forest {
+            // the unknown parent could only be at or below the height of the root
+            if node.height <= self.root.height.saturating_add(1) {
+                return Err(Rejection::Conflicting);
+            }
+            if node.height > self.height.saturating_add(self.max_orphan_distance) {
+                return Err(Rejection::TooFarAhead);
+            }
+            if self.buffer.len() >= self.buffer_limit {
+                return Err(Rejection::BufferFull);
+            }
+        }
+        if lower_placeholder {
+            self.move_placeholder(root_height);
+        }
+        Ok(())
+    }
+
+    /// Attaches an admitted node to its parent, or puts it into the buffer.
+    /// Returns whether the node was attached.
+    fn place(&mut self, node: ReorgNode<K, M, V>) -> bool {
+        // Only the topology is held in memory, the custom meta data goes into the store
+        let mut node = self.keep_body(node);
         // Retrieving the inserted nodes parent to append said node to the
         // parents list of children. If neither ifs trigger than parent is not part
         // of the system, and we put the node into the buffer.
@@ -427,66 +1260,675 @@ impl<K: Default + Eq + Hash + Clone + Debug + Copy, M: Debug + Default> Organize
             parent.children.push(node.key);
         } else if node.parent == self.root.key {
             self.root.children.push(node.key);
+        } else if self.forest {
+            // Without a known parent the node becomes a candidate root in the forest
+            self.forest_parents.insert(node.key, node.parent);
+            node.parent = self.root.key;
+            self.root.children.push(node.key);
         } else {
             self.buffer.insert(node.key, node);
-            return;
+            return false;
         }
+        self.accumulate(&mut node);
         // We save the node key to its height
-        match self.nodes_by_height.get_mut(&node.height) {
-            Some(has_node) => has_node.push(node.key),
+        self.index_height(node.height, node.key);
+        // If this is the newest node we take its height as the new system height
+        self.height = self.height.max(node.height);
+        // We save the node itself with its key as the key
+        let key = node.key;
+        self.nodes_by_key.insert(key, node);
+        if self.forest {
+            self.converge(key);
+        }
+        true
+    }
+
+    /// Moves the root forward as long as it is older than the allowed oldest height.
+    /// At each step, if the root has only one child, the child becomes the new root,
+    /// otherwise the child that heirs the longest lineage does, while every other
+    /// branch stemming from the root is abandoned and removed.
+    /// Returns the keys of the nodes that became the root, the oldest first.
+    pub fn advance_root(&mut self, most_valuable: Option<bool>) -> Vec<K> {
+        self.advance_root_along(Vec::new(), most_valuable)
+    }
+
+    /// Moves the root forward down the lineage of the lead head, the worthiest one,
+    /// without evaluating the fork choice again.
+    fn advance_to_lead(&mut self, lead: K, most_valuable: Option<bool>) -> Vec<K> {
+        let mut lineage = Vec::new();
+        let mut cursor = lead;
+        while let Some(node) = self.nodes_by_key.get(&cursor) {
+            lineage.push(cursor);
+            cursor = node.parent;
+        }
+        self.advance_root_along(lineage, most_valuable)
+    }
+
+    /// Moves the root forward like [`Organizer::advance_root`], but into the nodes of
+    /// the lineage as long as it lasts, without evaluating the fork choice at each step.
+    /// The lineage is ordered from its highest node down to a child of the root.
+    fn advance_root_along(&mut self, mut lineage: Vec<K>, most_valuable: Option<bool>) -> Vec<K> {
+        let mut finalized = Vec::new();
+        let head = self.head;
+        while self.root.height < self.allowed_oldest() && !self.root.children.is_empty() {
+            let next = match lineage.pop() {
+                Some(next) => next,
+                None if self.root.children.len() == 1 => self.root.children[0],
+                None => self.find_longest_branch(most_valuable),
+            };
+            let dead_branches: Vec<K> = self
+                .root
+                .children
+                .iter()
+                .filter(|child| **child != next)
+                .copied()
+                .collect();
+            // If this fails that means the children of the root were already removed.
+            let new_root = self
+                .nodes_by_key
+                .remove(&next)
+                .expect("the child of the root is not stored");
+            let old_root = std::mem::replace(&mut self.root, new_root);
+            self.unindex_height(old_root.height, &old_root.key);
+            self.store.remove(&old_root.key);
+            for dead_branch in dead_branches {
+                // we delete every branch stemming from the root other than the longest one
+                let abandoned = self.delete_children(&dead_branch);
+                self.notify_abandoned(&abandoned);
+            }
+            self.leave_forest();
+            self.notify_finalized(None);
+            self.record(Entry::Root(self.root.key));
+            finalized.push(self.root.key);
+        }
+        // The heads of a single branch are ranked the same from any root below them,
+        // but split over branches of the new root they may be ranked otherwise
+        let branches = self
+            .root
+            .children
+            .iter()
+            .filter_map(|child| self.nodes_by_key.get(child))
+            .filter(|child| {
+                self.slot_based || !child.children.is_empty() || child.height == self.height
+            })
+            .count();
+        self.head = head.filter(|_| finalized.is_empty() || branches < 2);
+        finalized
+    }
+
+    /// Attaches the nodes in the buffer whose parents have been pushed into the system,
+    /// repeatedly, so that a whole buffered branch attaches at once.
+    /// Returns the keys of the attached nodes, and of the ones dropped for not being
+    /// above their parents.
+    fn resolve_buffer(&mut self) -> (Vec<K>, Vec<K>) {
+        let (mut attached, mut dropped) = (Vec::new(), Vec::new());
+        loop {
+            let reinsert: Vec<K> = self
+                .buffer
+                .values()
+                .filter(|buffer_node| self.nodes_by_key.contains_key(&buffer_node.parent))
+                .map(|buffer_node| buffer_node.key)
+                .collect();
+            if reinsert.is_empty() {
+                return (attached, dropped);
+            }
+            // If we found the parent of a node in the buffer, we save it
+            for r in reinsert {
+                if let Some(mut reinsertable) = self.buffer.remove(&r) {
+                    if self
+                        .get_topology(&reinsertable.parent)
+                        .is_some_and(|parent| reinsertable.height <= parent.height)
+                    {
+                        // The parent turned out not to be below the orphan
+                        self.store.remove(&r);
+                        *self
+                            .counters
+                            .rejections
+                            .entry(Rejection::NotAboveParent)
+                            .or_insert(0) += 1;
+                        self.blame(reinsertable.arrival.source, |tally| tally.invalid += 1);
+                        dropped.push(r);
+                        continue;
+                    }
+                    self.accumulate(&mut reinsertable);
+                    if let Some(parent) = self.nodes_by_key.get_mut(&reinsertable.parent) {
+                        parent.children.push(r);
+                    }
+                    self.index_height(reinsertable.height, r);
+                    self.height = self.height.max(reinsertable.height);
+                    self.nodes_by_key.insert(r, reinsertable);
+                    attached.push(r);
+                }
+            }
+        }
+    }
+
+    /// Returns the operational statistics: the size and the shape of the tree, and
+    /// the counters of reorganizations, pruned nodes, expired orphans and refused inserts.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode, Rejection};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// organizer.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    /// organizer.insert(ReorgNode::new(3, 1, 0, 1, ()), None);
+    /// organizer.insert(ReorgNode::new(4, 2, 0, 3, ()), None);
+    /// organizer.insert(ReorgNode::new(5, 0, 0, 1, ()), None);
+    /// let stats = organizer.stats();
+    /// assert_eq!((stats.nodes, stats.tips, stats.forks), (4, 2, 1));
+    /// assert_eq!((stats.reorgs, stats.deepest_reorg, stats.max_fork_length), (1, 1, 1));
+    /// assert_eq!(stats.rejected(Rejection::TooOld), 1);
+    /// ```
+    pub fn stats(&self) -> Stats {
+        let canonical: HashSet<K> = self.canonical_chain().into_iter().collect();
+        let mut stats = self.counters.clone();
+        stats.nodes = self.nodes_by_key.len() + 1;
+        stats.buffered = self.buffer.len();
+        for node in self
+            .nodes_by_key
+            .values()
+            .chain(std::iter::once(&self.root))
+        {
+            if node.children.len() > 1 {
+                stats.forks += 1;
+            }
+            if !node.children.is_empty() {
+                continue;
+            }
+            stats.tips += 1;
+            // The length of a fork is counted down to where it leaves the canonical chain
+            let mut length = 0;
+            let mut cursor = node;
+            while !canonical.contains(&cursor.key) {
+                length += 1;
+                match self.get_topology(&cursor.parent) {
+                    Some(parent) => cursor = parent,
+                    None => break,
+                }
+            }
+            stats.max_fork_length = stats.max_fork_length.max(length);
+        }
+        stats
+    }
+
+    /// Getter for the keys to the nodes at the current greatest height.
+    pub fn highest_nodes(&self) -> &[K] {
+        self.nodes_by_height
+            .get(&self.height)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Getter for the current root with its custom meta data, read from the store.
+    /// See [`Organizer::topology`] to borrow the root without its meta data.
+    pub fn root(&self) -> ReorgNode<K, M, V>
+    where
+        M: Clone,
+    {
+        self.materialize(&self.root)
+    }
+
+    /// Getter for the topology of the current root.
+    pub fn topology(&self) -> &ReorgNode<K, (), V> {
+        &self.root
+    }
+
+    /// Getter for the greatest height in the system.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Getter for a node in the tree by its key with its custom meta data, read from
+    /// the store, the root included. Nodes waiting in the buffer are not returned.
+    /// See [`Organizer::get_topology`] to borrow the node without its meta data.
+    pub fn get(&self, key: &K) -> Option<ReorgNode<K, M, V>>
+    where
+        M: Clone,
+    {
+        self.get_topology(key).map(|node| self.materialize(node))
+    }
+
+    /// Getter for the topology of a node in the tree by its key, the root included.
+    /// Nodes waiting in the buffer are not returned.
+    pub fn get_topology(&self, key: &K) -> Option<&ReorgNode<K, (), V>> {
+        if *key == self.root.key {
+            Some(&self.root)
+        } else {
+            self.nodes_by_key.get(key)
+        }
+    }
+
+    /// Getter for the custom meta data of a node in the tree, read from the store.
+    pub fn meta(&self, key: &K) -> Option<S::Body<'_>> {
+        self.get_topology(key)
+            .and_then(|node| self.store.get(&node.key))
+    }
+
+    /// Returns the sum of the values of the stored node and its ancestors above the
+    /// current root, which is zero for the root itself.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 2, true);
+    /// for i in 1..=4 {
+    ///     organizer.insert(ReorgNode::new(i, i, u64::MAX, i - 1, ()), None);
+    /// }
+    /// assert_eq!(organizer.root().key(), &2);
+    /// assert_eq!(organizer.value_above_root(&4), Some(2 * u128::from(u64::MAX)));
+    /// assert_eq!(organizer.height_above_root(&4), Some(2));
+    /// assert_eq!(organizer.get(&4).unwrap().cumulative_height(), 4);
+    /// ```
+    pub fn value_above_root(&self, key: &K) -> Option<V::Sum> {
+        self.get_topology(key)
+            .map(|node| V::sub(&node.cumulative_value, &self.root.cumulative_value))
+    }
+
+    /// Returns the number of nodes from the current root up to the stored node.
+    pub fn height_above_root(&self, key: &K) -> Option<u64> {
+        self.get_topology(key)
+            .map(|node| node.cumulative_height - self.root.cumulative_height)
+    }
+
+    /// Getter for the tally of the useless submissions of the source.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// organizer.insert_from(ReorgNode::new(2, 1, 0, 1, ()), Some(7), None, None);
+    /// organizer.insert_from(ReorgNode::new(2, 1, 0, 1, ()), Some(7), None, None);
+    /// assert_eq!(organizer.misbehavior(7).duplicate, 1);
+    /// assert_eq!(organizer.misbehavior(8).total(), 0);
+    /// ```
+    pub fn misbehavior(&self, source: u64) -> Misbehavior {
+        self.misbehavior.get(&source).copied().unwrap_or_default()
+    }
+
+    /// Clears the tally of the source, for example after it was disconnected.
+    pub fn forgive(&mut self, source: u64) {
+        self.record(Entry::Forgive(source));
+        self.misbehavior.remove(&source);
+    }
+
+    /// Getter for the circumstances of the arrival of a stored or buffered node.
+    pub fn arrival(&self, key: &K) -> Option<Arrival> {
+        self.get_topology(key)
+            .or_else(|| self.buffer.get(key))
+            .map(|node| node.arrival)
+    }
+
+    /// Getter for the store holding the custom meta data of the nodes.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns whether the Organizer still holds multiple candidate roots under a placeholder.
+    pub fn is_forest(&self) -> bool {
+        self.forest
+    }
+
+    /// Returns whether every tip of the tree competes for the lead, see
+    /// [`OrganizerBuilder::slot_based`].
+    pub fn is_slot_based(&self) -> bool {
+        self.slot_based
+    }
+
+    /// Getter for the keys of the candidate roots. Outside of the forest mode this is
+    /// only the key of the root.
+    pub fn candidate_roots(&self) -> &[K] {
+        if self.forest {
+            &self.root.children
+        } else {
+            std::slice::from_ref(&self.root.key)
+        }
+    }
+
+    /// Flushes the journal, or returns the error of the first write that failed since
+    /// the last call. Once a write fails the journal stops recording, as it could no
+    /// longer be replayed into the same state.
+    pub fn sync_journal(&mut self) -> std::io::Result<()> {
+        match &mut self.journal {
+            Some(journal) => journal.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops recording the operations, flushing the journal first.
+    pub fn stop_journal(&mut self) -> std::io::Result<()> {
+        let result = self.sync_journal();
+        self.journal = None;
+        result
+    }
+
+    /// Records the operation into the journal, if one was started.
+    fn record(&mut self, entry: Entry<'_, K, M, V>) {
+        if let Some(journal) = &mut self.journal {
+            journal.record(entry);
+        }
+    }
+
+    /// Notifies the observers of a finalized node, or of the root if none is given.
+    fn notify_finalized(&mut self, node: Option<&ReorgNode<K, M, V>>) {
+        if self.observers.is_empty() {
+            return;
+        }
+        match node {
+            Some(node) => {
+                for observer in &mut self.observers {
+                    observer.finalized(node);
+                }
+            }
             None => {
-                self.nodes_by_height.insert(node.height, vec![node.key]);
+                // The body of the root is lent to the observers rather than cloned
+                let root = self.take_body(self.root.clone());
+                for observer in &mut self.observers {
+                    observer.finalized(&root);
+                }
+                self.keep_body(root);
+            }
+        }
+    }
+
+    /// Notifies the observers of the nodes removed with an abandoned branch.
+    fn notify_abandoned(&mut self, nodes: &[ReorgNode<K, M, V>]) {
+        for observer in &mut self.observers {
+            for node in nodes {
+                observer.abandoned(node);
+            }
+        }
+    }
+
+    /// Moves the candidate roots that descend from the designated node under it.
+    fn converge(&mut self, key: K) {
+        let height = self.nodes_by_key.get(&key).map_or(0, |node| node.height);
+        // Candidates that are not above the node stay in the forest
+        let converging: Vec<K> = self
+            .forest_parents
+            .iter()
+            .filter(|(candidate, parent)| {
+                **parent == key
+                    && self
+                        .nodes_by_key
+                        .get(candidate)
+                        .is_some_and(|candidate| candidate.height > height)
+            })
+            .map(|(candidate, _)| *candidate)
+            .collect();
+        for candidate in converging {
+            self.forest_parents.remove(&candidate);
+            self.root.children.retain(|c| *c != candidate);
+            if let Some(node) = self.nodes_by_key.get_mut(&candidate) {
+                node.parent = key;
+            }
+            if let Some(parent) = self.nodes_by_key.get_mut(&key) {
+                parent.children.push(candidate);
+            }
+            // The converged branch now accumulates on top of its real ancestors
+            let mut branch = vec![candidate];
+            while let Some(key) = branch.pop() {
+                if let Some(mut node) = self.nodes_by_key.remove(&key) {
+                    self.accumulate(&mut node);
+                    branch.extend(node.children.iter().copied());
+                    self.nodes_by_key.insert(key, node);
+                }
             }
+        }
+    }
+
+    /// Computes the cumulative value and height of a node from those of its parent.
+    fn accumulate(&self, node: &mut ReorgNode<K, (), V>) {
+        if let Some(parent) = self.get_topology(&node.parent) {
+            node.cumulative_value = V::add(&parent.cumulative_value, &node.value);
+            node.cumulative_height = parent.cumulative_height + 1;
+        }
+    }
+
+    /// Moves the placeholder root of the forest to the designated height.
+    fn move_placeholder(&mut self, height: u64) {
+        let key = self.root.key;
+        self.unindex_height(self.root.height, &key);
+        self.root.height = height;
+        self.index_height(height, key);
+        // Without candidate roots the placeholder is the highest node
+        if self.root.children.is_empty() {
+            self.height = height;
+        }
+    }
+
+    /// Ends the forest mode after the root has been replaced by a real node,
+    /// restoring the original parent of the new root.
+    fn leave_forest(&mut self) {
+        if self.forest {
+            if let Some(parent) = self.forest_parents.remove(&self.root.key) {
+                self.root.parent = parent;
+            }
+            self.forest_parents.clear();
+            self.forest = false;
+        }
+    }
+
+    /// Reads the custom meta data of a node that is held in the system from the store.
+    pub(crate) fn body(&self, key: &K) -> S::Body<'_> {
+        self.store
+            .get(key)
+            .expect("the body of a node is missing from the store")
+    }
+
+    /// Copies the topology of a node that is held in the system with its custom meta data.
+    fn materialize(&self, node: &ReorgNode<K, (), V>) -> ReorgNode<K, M, V>
+    where
+        M: Clone,
+    {
+        node.clone().with_meta(M::clone(&self.body(&node.key)))
+    }
+
+    /// Joins the topology of a node that left the system with its custom meta data,
+    /// removing the meta data from the store.
+    fn take_body(&mut self, node: ReorgNode<K, (), V>) -> ReorgNode<K, M, V> {
+        let meta = self
+            .store
+            .remove(&node.key)
+            .expect("the body of a node is missing from the store");
+        node.with_meta(meta)
+    }
+
+    /// Puts the meta data of a node into the store, returning its topology.
+    fn keep_body(&mut self, node: ReorgNode<K, M, V>) -> ReorgNode<K, (), V> {
+        let (node, meta) = node.into_parts();
+        self.store.put(node.key, meta);
+        node
+    }
+
+    /// Drops the buffered nodes below the designated height, with their meta data.
+    fn expire_buffer(&mut self, oldest: u64) {
+        let store = &mut self.store;
+        let buffered = self.buffer.len();
+        let mut sources = Vec::new();
+        self.buffer.retain(|key, node| {
+            let keep = node.height >= oldest;
+            if !keep {
+                store.remove(key);
+                sources.push(node.arrival.source);
+            }
+            keep
+        });
+        self.counters.orphans_expired += (buffered - self.buffer.len()) as u64;
+        for source in sources {
+            self.blame(source, |tally| tally.expired_orphans += 1);
+        }
+    }
+
+    /// Tallies a useless submission of the source, if known, notifying the observers
+    /// when the source reaches the misbehavior threshold.
+    /// Counts an insert of the source refused for the designated reason. The refusal is
+    /// recorded on its own, as the refused node is not part of the journal.
+    pub(crate) fn reject(&mut self, source: Option<u64>, rejection: Rejection) {
+        self.record(Entry::Reject(source, rejection));
+        *self.counters.rejections.entry(rejection).or_insert(0) += 1;
+        match rejection {
+            Rejection::Invalid | Rejection::NotAboveParent => {
+                self.blame(source, |tally| tally.invalid += 1)
+            }
+            Rejection::Duplicate => self.blame(source, |tally| tally.duplicate += 1),
+            Rejection::TooOld => self.blame(source, |tally| tally.too_old += 1),
+            _ => {}
+        }
+    }
+
+    fn blame(&mut self, source: Option<u64>, count: fn(&mut Misbehavior)) {
+        let source = match source {
+            Some(source) => source,
+            None => return,
         };
-        // This is the newest node hence we take its height as the new system height
-        self.height = node.height;
-        // We save the node itself with its key as the key
-        self.nodes_by_key.insert(node.key, node);
-        // We double check for nodes that should have already been removed
-        self.nodes_by_key.remove(&self.root.parent);
-        if let Some(old_root) = self
-            .nodes_by_height
-            .remove(&(self.root.height.saturating_sub(1)))
+        let tally = self.misbehavior.entry(source).or_default();
+        let before = tally.total();
+        count(tally);
+        let tally = *tally;
+        if self
+            .misbehavior_threshold
+            .is_some_and(|threshold| before < threshold && tally.total() >= threshold)
         {
-            for old in old_root {
-                self.nodes_by_key.remove(&old);
+            for observer in &mut self.observers {
+                observer.misbehaved(source, &tally);
             }
         }
+    }
 
-        let mut reinsert = Vec::new();
-        let mut buffer_clear = Vec::new();
-        // We check the nodes in the buffer wether they have expired,
-        // then we check if their parents have been pushed into the system.
-        for (key, buffer_node) in &self.buffer {
-            if buffer_node.height < self.allowed_oldest() {
-                buffer_clear.push(*key);
-                continue;
+    /// Returns the canonical head, computing it only if the one seen after the last
+    /// insert could have changed since.
+    fn current_head(&mut self) -> K {
+        match self.head.filter(|head| self.get_topology(head).is_some()) {
+            Some(head) => head,
+            None => {
+                let head = self.canonical_head();
+                self.head = Some(head);
+                head
+            }
+        }
+    }
+
+    /// Finds the canonical head after nodes were attached, counting a reorganization
+    /// if it does not descend from the earlier head. If a single node was attached,
+    /// it is only compared to the earlier head, unless it grew another branch.
+    fn track_head(&mut self, old_head: K, attached: Option<K>) {
+        let new_head = match attached.and_then(|key| self.challenge_head(old_head, key)) {
+            Some(head) => head,
+            // A single node at the greatest height is the canonical head by itself
+            None => match self.nodes_by_height.get(&self.height) {
+                Some(heads) if heads.len() == 1 && !self.slot_based => heads[0],
+                _ => self.canonical_head(),
+            },
+        };
+        self.head = Some(new_head);
+        if new_head == old_head {
+            return;
+        }
+        let depth = self.fork_depth(old_head, new_head);
+        if depth > 0 {
+            self.counters.reorgs += 1;
+            self.counters.deepest_reorg = self.counters.deepest_reorg.max(depth);
+        }
+    }
+
+    /// Decides between the canonical head and a node attached after it was found,
+    /// or returns `None` if the node grew another branch of the root, or took the
+    /// place of the head without beating it, so that every head has to be evaluated.
+    fn challenge_head(&self, head: K, key: K) -> Option<K> {
+        let head = self.nodes_by_key.get(&head)?;
+        let node = self.nodes_by_key.get(&key)?;
+        if !self.slot_based {
+            // Only the nodes at the greatest height compete for the lead
+            if node.height < self.height && head.height == self.height {
+                return Some(head.key);
             }
-            if let Some(parent) = self.nodes_by_key.get_mut(&buffer_node.parent) {
-                parent.children.push(*key);
-                reinsert.push(*key);
+            if node.height != head.height {
+                return None;
             }
         }
-        // If we found the parent of a node in the buffer, we save it
-        for r in reinsert {
-            if let Some(reinsertable) = self.buffer.remove(&r) {
-                match self.nodes_by_height.get_mut(&reinsertable.height) {
-                    Some(has_node) => has_node.push(r),
-                    None => {
-                        self.nodes_by_height.insert(reinsertable.height, vec![r]);
-                    }
-                };
-                self.nodes_by_key.insert(r, reinsertable);
+        // Within the branch of the head the worth of the heads are measured from the same
+        // child of the root, so the cumulative counts decide, the first arrived on a tie.
+        let (mut cursor, mut ancestor) = (node, head);
+        while cursor.key != ancestor.key {
+            if cursor.height >= ancestor.height {
+                cursor = self.nodes_by_key.get(&cursor.parent)?;
+            } else {
+                ancestor = self.nodes_by_key.get(&ancestor.parent)?;
             }
         }
-        // If the node has expired we remove if from the buffer-
-        for bc in buffer_clear {
-            self.buffer.remove(&bc);
+        let gain = match self.fork_choice {
+            ForkChoice::Longest => node.cumulative_height.cmp(&head.cumulative_height),
+            ForkChoice::MostSlots => node.height.cmp(&head.height),
+            ForkChoice::MostValuable => node.cumulative_value.cmp(&head.cumulative_value),
+        };
+        if gain == Ordering::Greater {
+            Some(node.key)
+        } else if node.parent == head.key {
+            None
+        } else {
+            Some(head.key)
         }
     }
 
-    /// Getter for the keys to the nodes at the current greatest height.
-    pub fn highest_nodes(&self) -> &[K] {
-        self.nodes_by_height.get(&self.height).unwrap()
+    /// Counts the nodes from the old head down to the common ancestor with the new head,
+    /// which is zero if the new head descends from the old one.
+    fn fork_depth(&self, old_head: K, new_head: K) -> u64 {
+        let (mut old, mut new) = match (self.get_topology(&old_head), self.get_topology(&new_head))
+        {
+            (Some(old), Some(new)) => (old, new),
+            _ => return 0,
+        };
+        let mut depth = 0;
+        while old.key != new.key {
+            if new.height > old.height {
+                match self.get_topology(&new.parent) {
+                    Some(parent) => new = parent,
+                    None => break,
+                }
+            } else {
+                match self.get_topology(&old.parent) {
+                    Some(parent) => old = parent,
+                    None => break,
+                }
+                depth += 1;
+            }
+        }
+        depth
+    }
+
+    /// Removes the designated child from the children of its parent, if the parent is held.
+    fn unlink_child(&mut self, parent: &K, child: &K) {
+        if let Some(parent) = self.nodes_by_key.get_mut(parent) {
+            parent.children.retain(|c| c != child);
+        } else if *parent == self.root.key {
+            self.root.children.retain(|c| c != child);
+        }
+    }
+
+    /// Saves the key of a node to its height.
+    fn index_height(&mut self, height: u64, key: K) {
+        self.nodes_by_height.entry(height).or_default().push(key);
+    }
+
+    /// Removes the key of a node from its height, dropping the height if it became empty.
+    fn unindex_height(&mut self, height: u64, key: &K) {
+        if let Some(keys) = self.nodes_by_height.get_mut(&height) {
+            keys.retain(|k| k != key);
+            if keys.is_empty() {
+                self.nodes_by_height.remove(&height);
+            }
+        }
+    }
+
+    /// Sets the height of the system to the greatest height that still has nodes,
+    /// used after nodes have been removed from the top of the tree.
+    fn recompute_height(&mut self) {
+        if !self.nodes_by_height.contains_key(&self.height) {
+            self.height = self
+                .nodes_by_height
+                .keys()
+                .copied()
+                .max()
+                .unwrap_or(self.root.height);
+        }
     }
 }
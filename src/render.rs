@@ -0,0 +1,337 @@
+//! Visualizations of the fork tree of an [`Organizer`] for debugging: a Graphviz
+//! graph and a plain text tree. The canonical chain is highlighted in both, and
+//! the buffered nodes are shown detached from the tree.
+//! Listings of the stored nodes can be written into any sink as well.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Write};
+use std::hash::Hash;
+use std::io;
+
+use crate::{NodeStore, Organizer, ReorgNode, Weight};
+
+/// Quotes the key as a Graphviz identifier.
+fn dot_id<K: Debug>(key: &K) -> String {
+    let mut id = String::from("\"");
+    for c in format!("{:?}", key).chars() {
+        if c == '"' || c == '\\' {
+            id.push('\\');
+        }
+        id.push(c);
+    }
+    id.push('"');
+    id
+}
+
+/// Quoted Graphviz label of the node, holding its key and its height.
+fn dot_label<K: Debug, V: Weight>(node: &ReorgNode<K, (), V>) -> String {
+    let id = dot_id(&node.key);
+    format!("{}\\nheight {}\"", &id[..id.len() - 1], node.height)
+}
+
+/// Adapter writing formatted text into an [`io::Write`] sink, keeping the I/O error
+/// that the formatting machinery can not carry.
+struct IoAdapter<'a, W> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+/// Runs the formatting into an [`io::Write`] sink, returning the error of the sink.
+fn write_io<W, F>(out: &mut W, format: F) -> io::Result<()>
+where
+    W: io::Write,
+    F: FnOnce(&mut IoAdapter<'_, W>) -> fmt::Result,
+{
+    let mut adapter = IoAdapter {
+        inner: out,
+        error: None,
+    };
+    match format(&mut adapter) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(adapter
+            .error
+            .unwrap_or_else(|| io::Error::other("formatting failed"))),
+    }
+}
+
+impl<K, M, S, V> Organizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy,
+    M: Debug + Default,
+    S: NodeStore<K, M>,
+    V: Weight,
+{
+    /// Returns a Graphviz graph of the tree, with the edges pointing from the parents
+    /// to the children. The root is drawn bold, the canonical chain in red, and the
+    /// buffered nodes dashed in a separate cluster, without edges.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// organizer.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    /// assert!(organizer.to_dot().contains("\"1\" -> \"2\" [color=red, penwidth=2];"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        self.write_dot(&mut dot)
+            .expect("writing into a String does not fail");
+        dot
+    }
+
+    /// Writes the Graphviz graph of [`Organizer::to_dot`] into the output.
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> fmt::Result {
+        let canonical: HashSet<K> = self.canonical_chain().into_iter().collect();
+        writeln!(out, "digraph reorg {{")?;
+        writeln!(out, "    node [shape=box];")?;
+        for node in self.tree_order() {
+            let mut attributes = format!("label={}", dot_label(node));
+            if node.key == self.root.key {
+                attributes.push_str(", style=bold");
+            }
+            if canonical.contains(&node.key) {
+                attributes.push_str(", color=red");
+            }
+            writeln!(out, "    {} [{}];", dot_id(&node.key), attributes)?;
+            if node.key != self.root.key {
+                let style = if canonical.contains(&node.key) {
+                    " [color=red, penwidth=2]"
+                } else {
+                    ""
+                };
+                writeln!(
+                    out,
+                    "    {} -> {}{};",
+                    dot_id(&node.parent),
+                    dot_id(&node.key),
+                    style
+                )?;
+            }
+        }
+        if !self.buffer.is_empty() {
+            writeln!(out, "    subgraph cluster_buffer {{")?;
+            writeln!(out, "        label=\"buffer\";")?;
+            writeln!(out, "        style=dashed;")?;
+            for node in self.buffered_order() {
+                writeln!(
+                    out,
+                    "        {} [label={}, style=dashed];",
+                    dot_id(&node.key),
+                    dot_label(node)
+                )?;
+            }
+            writeln!(out, "    }}")?;
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Writes the tree as text into the output, one node per line. Branches are drawn
+    /// where a node has more than one child, a lone child is written right under its
+    /// parent. The nodes of the canonical chain are marked with a `*`, and the buffered
+    /// nodes are listed after the tree with the parents they wait for.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// organizer.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    /// organizer.insert(ReorgNode::new(3, 1, 0, 1, ()), None);
+    /// organizer.insert(ReorgNode::new(4, 2, 0, 2, ()), None);
+    /// let mut tree = String::new();
+    /// organizer.write_tree(&mut tree).unwrap();
+    /// assert_eq!(tree, "1 (height 0) *\n|-- 2 (height 1) *\n|   4 (height 2) *\n`-- 3 (height 1)\n");
+    /// ```
+    pub fn write_tree<W: Write>(&self, out: &mut W) -> fmt::Result {
+        let canonical: HashSet<K> = self.canonical_chain().into_iter().collect();
+        // Every entry holds the key, the prefix of the line, the connector of the
+        // node and the prefix its descendants continue with.
+        let mut stack = vec![(self.root.key, String::new(), "", "")];
+        while let Some((key, prefix, connector, continuation)) = stack.pop() {
+            let node = match self.get_topology(&key) {
+                Some(node) => node,
+                None => continue,
+            };
+            write!(
+                out,
+                "{}{}{:?} (height {})",
+                prefix, connector, node.key, node.height
+            )?;
+            if canonical.contains(&key) {
+                write!(out, " *")?;
+            }
+            writeln!(out)?;
+            let prefix = format!("{}{}", prefix, continuation);
+            match node.children.as_slice() {
+                [] => {}
+                [child] => stack.push((*child, prefix, "", "")),
+                children => {
+                    for (i, child) in children.iter().enumerate().rev() {
+                        if i == children.len() - 1 {
+                            stack.push((*child, prefix.clone(), "`-- ", "    "));
+                        } else {
+                            stack.push((*child, prefix.clone(), "|-- ", "|   "));
+                        }
+                    }
+                }
+            }
+        }
+        if !self.buffer.is_empty() {
+            writeln!(out, "buffer:")?;
+            for node in self.buffered_order() {
+                writeln!(
+                    out,
+                    "    {:?} (height {}) waiting for {:?}",
+                    node.key, node.height, node.parent
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the nodes of the tree from the root, every parent before its children.
+    fn tree_order(&self) -> Vec<&ReorgNode<K, (), V>> {
+        let mut order = Vec::new();
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .filter_map(|child| self.nodes_by_key.get(child)),
+            );
+        }
+        order
+    }
+
+    /// Returns the buffered nodes sorted by their heights, then by their arrivals.
+    fn buffered_order(&self) -> Vec<&ReorgNode<K, (), V>> {
+        let mut buffered: Vec<&ReorgNode<K, (), V>> = self.buffer.values().collect();
+        buffered.sort_by_key(|node| (node.height, node.arrival.sequence));
+        buffered
+    }
+
+    /// Utility function that lists node stored by their keyes. (Only prints the keyes)
+    /// The keys are sorted by the heights of their nodes only, see
+    /// [`Organizer::write_node_keys`] to sort them by the keys as well.
+    pub fn list_node_keyes(&self) {
+        write_io(&mut io::stdout().lock(), |out| {
+            self.fmt_node_keys_by(out, |_, _| Ordering::Equal)
+        })
+        .expect("failed printing to stdout");
+    }
+
+    /// Utility that prints the node stored by their keyes. (Actually displays the nodes)
+    /// The nodes are sorted by their heights only, see [`Organizer::write_nodes`] to
+    /// sort them by their keys as well.
+    pub fn list_nodes(&self) {
+        write_io(&mut io::stdout().lock(), |out| {
+            self.fmt_nodes_by(out, false, |_, _| Ordering::Equal)
+        })
+        .expect("failed printing to stdout");
+    }
+
+    /// Formats the keys of the stored nodes sorted by their heights, then by the comparison.
+    fn fmt_node_keys_by<W, F>(&self, out: &mut W, compare: F) -> fmt::Result
+    where
+        W: Write,
+        F: FnMut(&K, &K) -> Ordering,
+    {
+        for node in self.sorted_nodes_by(compare) {
+            writeln!(out, "{:?}", node.key)?;
+        }
+        Ok(())
+    }
+
+    /// Formats the stored nodes sorted by their heights, then by the comparison of their keys.
+    fn fmt_nodes_by<W, F>(&self, out: &mut W, compact: bool, compare: F) -> fmt::Result
+    where
+        W: Write,
+        F: FnMut(&K, &K) -> Ordering,
+    {
+        for node in self.sorted_nodes_by(compare) {
+            let meta = self.body(&node.key);
+            if compact {
+                writeln!(
+                    out,
+                    "{:?} height {} value {:?} parent {:?} children {:?} meta {:?}",
+                    node.key, node.height, node.value, node.parent, node.children, *meta
+                )?;
+            } else {
+                writeln!(out, "{}\n", node.clone().with_meta(&*meta))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the nodes stored by their keys, sorted by their heights, then by the
+    /// comparison of their keys.
+    fn sorted_nodes_by<F>(&self, mut compare: F) -> Vec<&ReorgNode<K, (), V>>
+    where
+        F: FnMut(&K, &K) -> Ordering,
+    {
+        let mut nodes: Vec<&ReorgNode<K, (), V>> = self.nodes_by_key.values().collect();
+        nodes.sort_by(|a, b| {
+            a.height
+                .cmp(&b.height)
+                .then_with(|| compare(&a.key, &b.key))
+        });
+        nodes
+    }
+}
+
+impl<K, M, S, V> Organizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy + Ord,
+    M: Debug + Default,
+    S: NodeStore<K, M>,
+    V: Weight,
+{
+    /// Writes the keys of the nodes stored by their keys, the root not included,
+    /// one per line, sorted by their heights, then by their keys.
+    pub fn write_node_keys<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        write_io(out, |out| self.fmt_node_keys(out))
+    }
+
+    /// Writes the nodes stored by their keys, the root not included, sorted by their
+    /// heights, then by their keys. Every node is written on its own line if compact,
+    /// otherwise as displayed, followed by an empty line.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, u8>::new_with(ReorgNode::new(1, 0, 0, 0, 0), 777, false);
+    /// organizer.insert(ReorgNode::new(3, 1, 5, 1, 7), None);
+    /// organizer.insert(ReorgNode::new(2, 1, 0, 1, 0), None);
+    /// let mut out = Vec::new();
+    /// organizer.write_nodes(&mut out, true).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(out).unwrap(),
+    ///     "2 height 1 value 0 parent 1 children [] meta 0\n\
+    ///      3 height 1 value 5 parent 1 children [] meta 7\n"
+    /// );
+    /// ```
+    pub fn write_nodes<W: io::Write>(&self, out: &mut W, compact: bool) -> io::Result<()> {
+        write_io(out, |out| self.fmt_nodes(out, compact))
+    }
+
+    /// Formats the keys as [`Organizer::write_node_keys`] does.
+    pub fn fmt_node_keys<W: Write>(&self, out: &mut W) -> fmt::Result {
+        self.fmt_node_keys_by(out, K::cmp)
+    }
+
+    /// Formats the nodes as [`Organizer::write_nodes`] does.
+    pub fn fmt_nodes<W: Write>(&self, out: &mut W, compact: bool) -> fmt::Result {
+        self.fmt_nodes_by(out, compact, K::cmp)
+    }
+}
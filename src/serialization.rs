@@ -0,0 +1,152 @@
+//! Optional serde support, enabled by the `serde` feature.
+//! The [`Organizer`] is (de)serialized through a representation that holds its
+//! state as sequences, so that formats without non-string map keys work as well.
+//! Validators, observers, the tie breaker and the buffer limits are not serialized,
+//! a deserialized organizer starts with the defaults.
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::Deref;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::snapshot::linked;
+use crate::{ForkChoice, Misbehavior, NodeStore, Organizer, ReorgNode, Weight};
+
+/// Meta data read from the store, serialized as the meta data itself.
+struct Body<B>(B);
+
+impl<B: Deref> Serialize for Body<B>
+where
+    B::Target: Serialize,
+{
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        (*self.0).serialize(serializer)
+    }
+}
+
+/// Representation of the state of an [`Organizer`] for serialization, borrowing
+/// everything but the nodes, which are joined with their meta data from the store.
+#[derive(Serialize)]
+#[serde(bound = "K: Serialize, Body<B>: Serialize, V: Serialize, V::Sum: Serialize")]
+struct OrganizerRef<'a, K, B, V: Weight> {
+    root: ReorgNode<K, Body<B>, V>,
+    nodes: Vec<ReorgNode<K, Body<B>, V>>,
+    nodes_by_height: Vec<(u64, &'a [K])>,
+    buffer: Vec<ReorgNode<K, Body<B>, V>>,
+    height: u64,
+    allowed_depth: u64,
+    fork_choice: ForkChoice,
+    forest: bool,
+    forest_parents: Vec<(&'a K, &'a K)>,
+    arrivals: u64,
+    slot_based: bool,
+    misbehavior: Vec<(&'a u64, &'a Misbehavior)>,
+}
+
+/// Owned representation of the state of an [`Organizer`] for deserialization.
+#[derive(Deserialize)]
+#[serde(
+    bound = "K: Deserialize<'de>, M: Deserialize<'de>, V: Deserialize<'de>, V::Sum: Deserialize<'de>"
+)]
+struct OrganizerRepr<K, M, V: Weight> {
+    root: ReorgNode<K, M, V>,
+    nodes: Vec<ReorgNode<K, M, V>>,
+    nodes_by_height: Vec<(u64, Vec<K>)>,
+    buffer: Vec<ReorgNode<K, M, V>>,
+    height: u64,
+    allowed_depth: u64,
+    fork_choice: ForkChoice,
+    forest: bool,
+    forest_parents: Vec<(K, K)>,
+    #[serde(default)]
+    arrivals: u64,
+    #[serde(default)]
+    slot_based: bool,
+    #[serde(default)]
+    misbehavior: Vec<(u64, Misbehavior)>,
+}
+
+impl<K, M, S, V> Serialize for Organizer<K, M, S, V>
+where
+    K: Serialize + Default + Eq + Hash + Clone + Debug + Copy,
+    M: Serialize + Debug + Default,
+    S: NodeStore<K, M>,
+    V: Serialize + Weight,
+    V::Sum: Serialize,
+{
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let assemble =
+            |node: &ReorgNode<K, (), V>| node.clone().with_meta(Body(self.body(&node.key)));
+        OrganizerRef {
+            root: assemble(&self.root),
+            nodes: self.nodes_by_key.values().map(assemble).collect(),
+            nodes_by_height: self
+                .nodes_by_height
+                .iter()
+                .map(|(height, keys)| (*height, keys.as_slice()))
+                .collect(),
+            buffer: self.buffer.values().map(assemble).collect(),
+            height: self.height,
+            allowed_depth: self.allowed_depth,
+            fork_choice: self.fork_choice,
+            forest: self.forest,
+            forest_parents: self.forest_parents.iter().collect(),
+            arrivals: self.arrivals,
+            slot_based: self.slot_based,
+            misbehavior: self.misbehavior.iter().collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, K, M, S, V> Deserialize<'de> for Organizer<K, M, S, V>
+where
+    K: Deserialize<'de> + Default + Eq + Hash + Clone + Debug + Copy,
+    M: Deserialize<'de> + Debug + Default,
+    S: NodeStore<K, M> + Default,
+    V: Deserialize<'de> + Weight,
+    V::Sum: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = OrganizerRepr::<K, M, V>::deserialize(deserializer)?;
+        if repr.allowed_depth == 0 {
+            return Err(serde::de::Error::custom(
+                "the allowed depth must be greater than zero",
+            ));
+        }
+        let mut organizer = Organizer::with_store(S::default(), repr.allowed_depth);
+        organizer.store.remove(&organizer.root.key);
+        organizer.root = organizer.keep_body(repr.root);
+        for node in repr.nodes {
+            let node = organizer.keep_body(node);
+            organizer.nodes_by_key.insert(node.key, node);
+        }
+        for node in repr.buffer {
+            let node = organizer.keep_body(node);
+            organizer.buffer.insert(node.key, node);
+        }
+        organizer.nodes_by_height = repr.nodes_by_height.into_iter().collect();
+        organizer.height = repr.height;
+        organizer.fork_choice = repr.fork_choice;
+        organizer.forest = repr.forest;
+        organizer.forest_parents = repr.forest_parents.into_iter().collect();
+        organizer.arrivals = repr.arrivals;
+        organizer.slot_based = repr.slot_based;
+        organizer.misbehavior = repr.misbehavior.into_iter().collect();
+        let height_indexed =
+            organizer.slot_based || organizer.nodes_by_height.contains_key(&organizer.height);
+        if !height_indexed
+            || !linked(
+                &organizer.root,
+                &organizer.nodes_by_key,
+                &organizer.nodes_by_height,
+            )
+        {
+            return Err(serde::de::Error::custom(
+                "the nodes do not form a linked and indexed tree",
+            ));
+        }
+        Ok(organizer)
+    }
+}
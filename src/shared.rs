@@ -0,0 +1,190 @@
+//! Thread safe wrapper of the [`Organizer`], for when nodes are inserted on some
+//! threads while others query the head and the canonical chain.
+//! Every change publishes an immutable [`CanonicalChain`], so that readers of the
+//! head never wait for an insert to finish.
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{BatchOutcome, InsertOutcome, MemoryStore, NodeStore, Organizer, ReorgNode, Weight};
+
+/// Immutable view of the canonical chain, published after every change of a
+/// [`SharedOrganizer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalChain<K> {
+    /// The keys of the chain from the root up to the head.
+    keys: Vec<K>,
+    /// The height of the head.
+    height: u64,
+}
+
+impl<K> CanonicalChain<K> {
+    /// Getter for the key of the root, where the chain starts.
+    pub fn root(&self) -> &K {
+        &self.keys[0]
+    }
+
+    /// Getter for the key of the head, where the chain ends.
+    pub fn head(&self) -> &K {
+        &self.keys[self.keys.len() - 1]
+    }
+
+    /// Getter for the height of the head.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Getter for the keys of the chain from the root up to the head.
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// Returns whether the node with the designated key is part of the chain.
+    pub fn contains(&self, key: &K) -> bool
+    where
+        K: PartialEq,
+    {
+        self.keys.contains(key)
+    }
+}
+
+/// [`Organizer`] that can be shared between threads. Writers take turns through
+/// a lock, while the canonical chain is read from the last published snapshot.
+pub struct SharedOrganizer<K, M, S = MemoryStore<K, M>, V: Weight = u64> {
+    organizer: RwLock<Organizer<K, M, S, V>>,
+    chain: RwLock<Arc<CanonicalChain<K>>>,
+}
+
+impl<K, M, S, V> SharedOrganizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy,
+    M: Debug + Default,
+    S: NodeStore<K, M>,
+    V: Weight,
+{
+    /// Wraps the organizer, publishing its canonical chain.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode, SharedOrganizer};
+    ///
+    /// let organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// let shared = SharedOrganizer::new(organizer);
+    /// shared.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    /// assert_eq!(shared.chain().keys(), &[1, 2]);
+    /// ```
+    pub fn new(organizer: Organizer<K, M, S, V>) -> Self {
+        let chain = RwLock::new(Arc::new(Self::canonical(&organizer)));
+        SharedOrganizer {
+            organizer: RwLock::new(organizer),
+            chain,
+        }
+    }
+
+    /// Inserts the node, see [`Organizer::insert`].
+    pub fn insert(
+        &self,
+        node: ReorgNode<K, M, V>,
+        most_valuable: Option<bool>,
+    ) -> InsertOutcome<K> {
+        self.write(|organizer| organizer.insert(node, most_valuable))
+    }
+
+    /// Inserts the node with the source it came from and the time it arrived at,
+    /// see [`Organizer::insert_from`].
+    pub fn insert_from(
+        &self,
+        node: ReorgNode<K, M, V>,
+        source: Option<u64>,
+        timestamp: Option<u64>,
+        most_valuable: Option<bool>,
+    ) -> InsertOutcome<K> {
+        self.write(|organizer| organizer.insert_from(node, source, timestamp, most_valuable))
+    }
+
+    /// Inserts the nodes at once, see [`Organizer::insert_batch`].
+    pub fn insert_batch<I>(&self, nodes: I, most_valuable: Option<bool>) -> BatchOutcome<K>
+    where
+        I: IntoIterator<Item = ReorgNode<K, M, V>>,
+    {
+        self.write(|organizer| organizer.insert_batch(nodes, most_valuable))
+    }
+
+    /// Finalizes the node, see [`Organizer::finalize`].
+    pub fn finalize(&self, key: &K) -> Option<Vec<ReorgNode<K, M, V>>> {
+        self.write(|organizer| organizer.finalize(key))
+    }
+
+    /// Runs the function with exclusive access to the organizer, then publishes
+    /// the canonical chain. Readers of the chain are not blocked meanwhile.
+    pub fn write<T>(&self, f: impl FnOnce(&mut Organizer<K, M, S, V>) -> T) -> T {
+        let mut organizer = self.lock_write();
+        let ret = f(&mut organizer);
+        let chain = Arc::new(Self::canonical(&organizer));
+        *self.chain.write().unwrap_or_else(PoisonError::into_inner) = chain;
+        ret
+    }
+
+    /// Runs the function with shared access to the organizer, waiting for the
+    /// current writer to finish.
+    pub fn read<T>(&self, f: impl FnOnce(&Organizer<K, M, S, V>) -> T) -> T {
+        f(&self.lock_read())
+    }
+
+    /// Returns the last published canonical chain, without waiting for writers.
+    pub fn chain(&self) -> Arc<CanonicalChain<K>> {
+        Arc::clone(&self.chain.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Returns the key of the head of the last published canonical chain.
+    pub fn head(&self) -> K {
+        *self.chain().head()
+    }
+
+    /// Returns the height of the head of the last published canonical chain.
+    pub fn height(&self) -> u64 {
+        self.chain().height()
+    }
+
+    /// Unwraps the organizer.
+    pub fn into_inner(self) -> Organizer<K, M, S, V> {
+        self.organizer
+            .into_inner()
+            .expect("a writer of the organizer panicked")
+    }
+
+    /// Locks the organizer for writing, panicking if an earlier writer panicked.
+    fn lock_write(&self) -> RwLockWriteGuard<'_, Organizer<K, M, S, V>> {
+        self.organizer
+            .write()
+            .expect("a writer of the organizer panicked")
+    }
+
+    /// Locks the organizer for reading, panicking if an earlier writer panicked.
+    fn lock_read(&self) -> RwLockReadGuard<'_, Organizer<K, M, S, V>> {
+        self.organizer
+            .read()
+            .expect("a writer of the organizer panicked")
+    }
+
+    /// Collects the canonical chain of the organizer.
+    fn canonical(organizer: &Organizer<K, M, S, V>) -> CanonicalChain<K> {
+        let keys = organizer.canonical_chain();
+        let height = organizer
+            .get_topology(&keys[keys.len() - 1])
+            .map_or(0, |node| node.height);
+        CanonicalChain { keys, height }
+    }
+}
+
+impl<K, M, S, V> From<Organizer<K, M, S, V>> for SharedOrganizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy,
+    M: Debug + Default,
+    S: NodeStore<K, M>,
+    V: Weight,
+{
+    fn from(organizer: Organizer<K, M, S, V>) -> Self {
+        SharedOrganizer::new(organizer)
+    }
+}
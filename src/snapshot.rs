@@ -0,0 +1,502 @@
+//! Snapshot of the full state of an [`Organizer`] in a versioned binary format,
+//! so that the tree does not have to be rebuilt after a restart.
+//! The format only relies on std, keys and custom meta data are encoded through
+//! the [`Codec`] trait.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
+
+use crate::{
+    Arrival, ForkChoice, Misbehavior, NodeStore, Organizer, OrganizerBuilder, Rejection, ReorgNode,
+    Weight,
+};
+
+/// Leading bytes of every snapshot.
+const MAGIC: &[u8; 4] = b"ABRG";
+/// Version of the snapshot format written by this crate.
+const VERSION: u8 = 1;
+
+/// Reason for which a snapshot could not be restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnapshotError {
+    /// The bytes do not start like a snapshot.
+    UnknownFormat,
+    /// The snapshot was written in a version of the format this crate does not read.
+    UnsupportedVersion(u8),
+    /// The snapshot ended before every field could be read.
+    Truncated,
+    /// A field of the snapshot holds a value that makes no sense.
+    Malformed,
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::UnknownFormat => write!(f, "the bytes are not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Truncated => write!(f, "the snapshot ended unexpectedly"),
+            SnapshotError::Malformed => write!(f, "the snapshot holds an invalid value"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+/// Binary encoding of the keys and the custom meta data in snapshots.
+pub trait Codec: Sized {
+    /// Appends the encoded value to the output.
+    fn encode(&self, output: &mut Vec<u8>);
+
+    /// Reads a value from the start of the input, advancing the input past it.
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError>;
+}
+
+/// Splits the designated number of bytes off the start of the input.
+pub(crate) fn take<'a>(input: &mut &'a [u8], count: usize) -> Result<&'a [u8], SnapshotError> {
+    if input.len() < count {
+        return Err(SnapshotError::Truncated);
+    }
+    let (taken, rest) = input.split_at(count);
+    *input = rest;
+    Ok(taken)
+}
+
+impl Codec for () {
+    fn encode(&self, _output: &mut Vec<u8>) {}
+
+    fn decode(_input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        Ok(())
+    }
+}
+
+impl Codec for bool {
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::Malformed),
+        }
+    }
+}
+
+macro_rules! impl_codec_for_int {
+    ($($int:ty),*) => {
+        $(
+            impl Codec for $int {
+                fn encode(&self, output: &mut Vec<u8>) {
+                    output.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+                    let bytes = take(input, std::mem::size_of::<$int>())?;
+                    Ok(<$int>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_int!(u8, u16, u32, u64, u128);
+
+impl<const N: usize> Codec for [u8; N] {
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(self);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        Ok(take(input, N)?.try_into().unwrap())
+    }
+}
+
+impl Codec for ForkChoice {
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.push(match self {
+            ForkChoice::Longest => 0,
+            ForkChoice::MostValuable => 1,
+            ForkChoice::MostSlots => 2,
+        });
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(ForkChoice::Longest),
+            1 => Ok(ForkChoice::MostValuable),
+            2 => Ok(ForkChoice::MostSlots),
+            _ => Err(SnapshotError::Malformed),
+        }
+    }
+}
+
+impl Codec for Rejection {
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.push(match self {
+            Rejection::Duplicate => 0,
+            Rejection::TooOld => 1,
+            Rejection::Conflicting => 2,
+            Rejection::Invalid => 3,
+            Rejection::NotAboveParent => 4,
+            Rejection::BufferFull => 5,
+            Rejection::TooFarAhead => 6,
+        });
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(Rejection::Duplicate),
+            1 => Ok(Rejection::TooOld),
+            2 => Ok(Rejection::Conflicting),
+            3 => Ok(Rejection::Invalid),
+            4 => Ok(Rejection::NotAboveParent),
+            5 => Ok(Rejection::BufferFull),
+            6 => Ok(Rejection::TooFarAhead),
+            _ => Err(SnapshotError::Malformed),
+        }
+    }
+}
+
+impl<T: Codec> Codec for Option<T> {
+    fn encode(&self, output: &mut Vec<u8>) {
+        self.is_some().encode(output);
+        if let Some(value) = self {
+            value.encode(output);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        if bool::decode(input)? {
+            Ok(Some(T::decode(input)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Codec for Vec<u8> {
+    fn encode(&self, output: &mut Vec<u8>) {
+        (self.len() as u64).encode(output);
+        output.extend_from_slice(self);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        let len = decode_len(input)?;
+        Ok(take(input, len)?.to_vec())
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, output: &mut Vec<u8>) {
+        (self.len() as u64).encode(output);
+        output.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        String::from_utf8(Vec::<u8>::decode(input)?).map_err(|_| SnapshotError::Malformed)
+    }
+}
+
+/// Reads a length prefix, refusing lengths that could not fit into the input.
+pub(crate) fn decode_len(input: &mut &[u8]) -> Result<usize, SnapshotError> {
+    let len = u64::decode(input)?;
+    if len > input.len() as u64 {
+        return Err(SnapshotError::Truncated);
+    }
+    Ok(len as usize)
+}
+
+/// Encodes the topology of the node, its arrival and its cumulative values, followed
+/// by the designated meta data.
+pub(crate) fn encode_node<K, T, M, V>(node: &ReorgNode<K, T, V>, meta: &M, output: &mut Vec<u8>)
+where
+    K: Codec,
+    M: Codec,
+    V: Weight + Codec,
+    V::Sum: Codec,
+{
+    node.key.encode(output);
+    node.height.encode(output);
+    node.value.encode(output);
+    node.parent.encode(output);
+    (node.children.len() as u64).encode(output);
+    for child in &node.children {
+        child.encode(output);
+    }
+    node.arrival.sequence.encode(output);
+    node.arrival.timestamp.encode(output);
+    node.arrival.source.encode(output);
+    node.cumulative_value.encode(output);
+    node.cumulative_height.encode(output);
+    meta.encode(output);
+}
+
+pub(crate) fn decode_node<K, M, V>(input: &mut &[u8]) -> Result<ReorgNode<K, M, V>, SnapshotError>
+where
+    K: Codec,
+    M: Codec,
+    V: Weight + Codec,
+    V::Sum: Codec,
+{
+    let key = K::decode(input)?;
+    let height = u64::decode(input)?;
+    let value = V::decode(input)?;
+    let parent = K::decode(input)?;
+    let mut children = Vec::new();
+    for _ in 0..decode_len(input)? {
+        children.push(K::decode(input)?);
+    }
+    let arrival = Arrival {
+        sequence: u64::decode(input)?,
+        timestamp: Option::decode(input)?,
+        source: Option::decode(input)?,
+    };
+    let cumulative_value = V::Sum::decode(input)?;
+    let cumulative_height = u64::decode(input)?;
+    Ok(ReorgNode {
+        key,
+        height,
+        value,
+        parent,
+        children,
+        custom_meta: M::decode(input)?,
+        arrival,
+        cumulative_value,
+        cumulative_height,
+    })
+}
+
+impl<K, M, S, V> Organizer<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy + Codec,
+    M: Debug + Default + Codec,
+    S: NodeStore<K, M>,
+    V: Weight + Codec,
+    V::Sum: Codec,
+{
+    /// Serializes the root, every stored and buffered node with its arrival, the heights,
+    /// the allowed depth, the fork choice rule, the state of the forest mode, whether
+    /// it is slot based and the tallies of the misbehaving sources.
+    /// Validators, observers, the tie breaker and the buffer limits are not part of
+    /// the snapshot, see [`OrganizerBuilder::restore`] to restore with them. Neither are
+    /// the counters of the statistics, they start over on restore.
+    /// Examples
+    /// ```
+    /// use abandoning_reorg::{Organizer, ReorgNode};
+    ///
+    /// let mut organizer = Organizer::<u64, ()>::new_with(ReorgNode::new(1, 0, 0, 0, ()), 777, false);
+    /// organizer.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    /// let restored = Organizer::<u64, ()>::restore(&organizer.snapshot()).unwrap();
+    /// assert_eq!(restored.height(), 1);
+    /// ```
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(MAGIC);
+        output.push(VERSION);
+        self.allowed_depth.encode(&mut output);
+        self.height.encode(&mut output);
+        self.fork_choice.encode(&mut output);
+        self.forest.encode(&mut output);
+        self.slot_based.encode(&mut output);
+        self.arrivals.encode(&mut output);
+        encode_node(&self.root, &*self.body(&self.root.key), &mut output);
+        (self.nodes_by_key.len() as u64).encode(&mut output);
+        for node in self.nodes_by_key.values() {
+            encode_node(node, &*self.body(&node.key), &mut output);
+        }
+        (self.nodes_by_height.len() as u64).encode(&mut output);
+        for (height, keys) in &self.nodes_by_height {
+            height.encode(&mut output);
+            (keys.len() as u64).encode(&mut output);
+            for key in keys {
+                key.encode(&mut output);
+            }
+        }
+        (self.buffer.len() as u64).encode(&mut output);
+        for node in self.buffer.values() {
+            encode_node(node, &*self.body(&node.key), &mut output);
+        }
+        (self.forest_parents.len() as u64).encode(&mut output);
+        for (candidate, parent) in &self.forest_parents {
+            candidate.encode(&mut output);
+            parent.encode(&mut output);
+        }
+        (self.misbehavior.len() as u64).encode(&mut output);
+        for (source, tally) in &self.misbehavior {
+            source.encode(&mut output);
+            tally.invalid.encode(&mut output);
+            tally.duplicate.encode(&mut output);
+            tally.too_old.encode(&mut output);
+            tally.expired_orphans.encode(&mut output);
+        }
+        output
+    }
+
+    /// Recreates an organizer from a snapshot taken by [`Organizer::snapshot`],
+    /// with default validators, observers, tie breaker and buffer limits.
+    pub fn restore(snapshot: &[u8]) -> Result<Organizer<K, M, S, V>, SnapshotError>
+    where
+        S: Default,
+    {
+        OrganizerBuilder::new().restore(snapshot)
+    }
+
+    /// Replaces the state, the allowed depth and the fork choice rule with the ones
+    /// read from a snapshot, keeping the hooks. Nothing changes if the snapshot is invalid.
+    pub(crate) fn restore_state(&mut self, snapshot: &[u8]) -> Result<(), SnapshotError> {
+        let mut input = snapshot;
+        if take(&mut input, MAGIC.len()).map_err(|_| SnapshotError::UnknownFormat)? != MAGIC {
+            return Err(SnapshotError::UnknownFormat);
+        }
+        let version = u8::decode(&mut input)?;
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let allowed_depth = u64::decode(&mut input)?;
+        let height = u64::decode(&mut input)?;
+        let fork_choice = ForkChoice::decode(&mut input)?;
+        if allowed_depth == 0 {
+            return Err(SnapshotError::Malformed);
+        }
+        let forest = bool::decode(&mut input)?;
+        let slot_based = bool::decode(&mut input)?;
+        let arrivals = u64::decode(&mut input)?;
+        let root: ReorgNode<K, M, V> = decode_node(&mut input)?;
+        let mut nodes_by_key = HashMap::new();
+        for _ in 0..decode_len(&mut input)? {
+            let node: ReorgNode<K, M, V> = decode_node(&mut input)?;
+            nodes_by_key.insert(node.key, node);
+        }
+        let mut nodes_by_height = HashMap::new();
+        for _ in 0..decode_len(&mut input)? {
+            let height = u64::decode(&mut input)?;
+            let mut keys = Vec::new();
+            for _ in 0..decode_len(&mut input)? {
+                keys.push(K::decode(&mut input)?);
+            }
+            nodes_by_height.insert(height, keys);
+        }
+        let mut buffer = HashMap::new();
+        for _ in 0..decode_len(&mut input)? {
+            let node: ReorgNode<K, M, V> = decode_node(&mut input)?;
+            buffer.insert(node.key, node);
+        }
+        let mut forest_parents = HashMap::new();
+        for _ in 0..decode_len(&mut input)? {
+            let candidate = K::decode(&mut input)?;
+            let parent = K::decode(&mut input)?;
+            forest_parents.insert(candidate, parent);
+        }
+        let mut misbehavior = HashMap::new();
+        for _ in 0..decode_len(&mut input)? {
+            let source = u64::decode(&mut input)?;
+            let tally = Misbehavior {
+                invalid: u64::decode(&mut input)?,
+                duplicate: u64::decode(&mut input)?,
+                too_old: u64::decode(&mut input)?,
+                expired_orphans: u64::decode(&mut input)?,
+            };
+            misbehavior.insert(source, tally);
+        }
+        if !input.is_empty() || !linked(&root, &nodes_by_key, &nodes_by_height) {
+            return Err(SnapshotError::Malformed);
+        }
+        // The height is indexed unless the slots left gaps below it
+        if !slot_based && !nodes_by_height.contains_key(&height) {
+            return Err(SnapshotError::Malformed);
+        }
+        self.allowed_depth = allowed_depth;
+        self.height = height;
+        self.fork_choice = fork_choice;
+        self.forest = forest;
+        self.slot_based = slot_based;
+        self.arrivals = arrivals;
+        // The bodies of the replaced nodes are dropped from the store
+        let old_keys: Vec<K> = std::iter::once(self.root.key)
+            .chain(self.nodes_by_key.keys().copied())
+            .chain(self.buffer.keys().copied())
+            .collect();
+        for key in old_keys {
+            self.store.remove(&key);
+        }
+        self.root = self.keep_body(root);
+        self.nodes_by_key = nodes_by_key
+            .into_iter()
+            .map(|(key, node)| (key, self.keep_body(node)))
+            .collect();
+        self.nodes_by_height = nodes_by_height;
+        self.buffer = buffer
+            .into_iter()
+            .map(|(key, node)| (key, self.keep_body(node)))
+            .collect();
+        self.forest_parents = forest_parents;
+        self.misbehavior = misbehavior;
+        self.head = None;
+        Ok(())
+    }
+}
+
+/// Whether the restored tree holds together: the root is indexed, every indexed key
+/// is stored at its height and every parent and child link points both ways.
+pub(crate) fn linked<K, M, V>(
+    root: &ReorgNode<K, M, V>,
+    nodes_by_key: &HashMap<K, ReorgNode<K, M, V>>,
+    nodes_by_height: &HashMap<u64, Vec<K>>,
+) -> bool
+where
+    K: Eq + Hash,
+    V: Weight,
+{
+    let get = |key: &K| {
+        if *key == root.key {
+            Some(root)
+        } else {
+            nodes_by_key.get(key)
+        }
+    };
+    let root_indexed = !nodes_by_key.contains_key(&root.key)
+        && nodes_by_height
+            .get(&root.height)
+            .is_some_and(|keys| keys.contains(&root.key));
+    let indexed = nodes_by_height.iter().all(|(height, keys)| {
+        keys.iter()
+            .all(|key| get(key).is_some_and(|node| node.height == *height))
+    });
+    let children = std::iter::once(root)
+        .chain(nodes_by_key.values())
+        .all(|node| {
+            node.children.iter().all(|child| {
+                nodes_by_key
+                    .get(child)
+                    .is_some_and(|child| child.parent == node.key)
+            })
+        });
+    let parents = nodes_by_key
+        .values()
+        .all(|node| get(&node.parent).is_some_and(|parent| parent.children.contains(&node.key)));
+    root_indexed && indexed && children && parents
+}
+
+impl<K, M, S, V> OrganizerBuilder<K, M, S, V>
+where
+    K: Default + Eq + Hash + Clone + Debug + Copy + Codec,
+    M: Debug + Default + Codec,
+    S: NodeStore<K, M>,
+    V: Weight + Codec,
+    V::Sum: Codec,
+{
+    /// Creates an organizer with the configured validators, observers, tie breaker
+    /// and buffer limits, but with the state, the allowed depth and the fork choice
+    /// rule read from a snapshot taken by [`Organizer::snapshot`].
+    pub fn restore(self, snapshot: &[u8]) -> Result<Organizer<K, M, S, V>, SnapshotError> {
+        let mut organizer = self.into_organizer();
+        organizer.restore_state(snapshot)?;
+        Ok(organizer)
+    }
+}
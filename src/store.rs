@@ -0,0 +1,128 @@
+//! Storage of the custom meta data of the nodes, so that the bodies of the nodes
+//! do not have to be held in memory. The [`Organizer`](crate::Organizer) only keeps
+//! the topology of the tree, and reads the bodies from the store when they are needed.
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::Codec;
+
+/// Holds the custom meta data of the nodes by their keys.
+pub trait NodeStore<K, M> {
+    /// Handle to the meta data read from the store, a reference for the stores
+    /// holding it in memory, so that reading it does not require cloning it.
+    type Body<'a>: Deref<Target = M>
+    where
+        Self: 'a;
+
+    /// Returns the meta data stored for the key.
+    fn get(&self, key: &K) -> Option<Self::Body<'_>>;
+
+    /// Stores the meta data for the key, replacing the earlier one.
+    fn put(&mut self, key: K, meta: M);
+
+    /// Removes and returns the meta data stored for the key.
+    fn remove(&mut self, key: &K) -> Option<M>;
+}
+
+/// Store keeping the meta data in memory, the default of the [`Organizer`](crate::Organizer).
+#[derive(Debug, Clone)]
+pub struct MemoryStore<K, M> {
+    bodies: HashMap<K, M>,
+}
+
+impl<K, M> Default for MemoryStore<K, M> {
+    fn default() -> Self {
+        MemoryStore {
+            bodies: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, M> NodeStore<K, M> for MemoryStore<K, M> {
+    type Body<'a>
+        = &'a M
+    where
+        Self: 'a;
+
+    fn get(&self, key: &K) -> Option<&M> {
+        self.bodies.get(key)
+    }
+
+    fn put(&mut self, key: K, meta: M) {
+        self.bodies.insert(key, meta);
+    }
+
+    fn remove(&mut self, key: &K) -> Option<M> {
+        self.bodies.remove(key)
+    }
+}
+
+/// Store keeping the meta data of every node in its own file in a directory, the
+/// name of the file being the hex encoded key. Meant for testing and for small
+/// deployments, as it panics if the file system fails.
+#[derive(Debug, Clone)]
+pub struct FileStore<K, M> {
+    directory: PathBuf,
+    _marker: PhantomData<fn(K) -> M>,
+}
+
+impl<K, M> FileStore<K, M> {
+    /// Creates a store in the designated directory, creating the directory if needed.
+    pub fn new<P: AsRef<Path>>(directory: P) -> io::Result<Self> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(FileStore {
+            directory: directory.as_ref().to_path_buf(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Getter for the directory of the store.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+}
+
+impl<K: Codec, M: Codec> FileStore<K, M> {
+    /// Path of the file holding the meta data of the key.
+    fn path(&self, key: &K) -> PathBuf {
+        let mut encoded = Vec::new();
+        key.encode(&mut encoded);
+        let name: String = encoded.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.directory.join(name)
+    }
+}
+
+impl<K: Codec, M: Codec> NodeStore<K, M> for FileStore<K, M> {
+    type Body<'a>
+        = Box<M>
+    where
+        Self: 'a;
+
+    fn get(&self, key: &K) -> Option<Box<M>> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Some(Box::new(
+                M::decode(&mut bytes.as_slice()).expect("a stored body could not be decoded"),
+            )),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => panic!("failed to read a stored body: {}", error),
+        }
+    }
+
+    fn put(&mut self, key: K, meta: M) {
+        let mut encoded = Vec::new();
+        meta.encode(&mut encoded);
+        fs::write(self.path(&key), encoded).expect("failed to write a stored body");
+    }
+
+    fn remove(&mut self, key: &K) -> Option<M> {
+        let meta = self.get(key)?;
+        fs::remove_file(self.path(key)).expect("failed to remove a stored body");
+        Some(*meta)
+    }
+}
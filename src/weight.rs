@@ -0,0 +1,98 @@
+//! Weights of the nodes, accumulated along the branches by the most valuable fork choice.
+
+use std::fmt::Debug;
+
+/// Value of a node, and how the values of a lineage add up. The [`Organizer`](crate::Organizer)
+/// only needs to start a sum, add a value to it, take a part of a sum away and compare
+/// two sums, so weights wider
+/// than a machine word, like the difficulties of proof of work chains, can be plugged in.
+/// The unsigned integers sum up into a `u128`, saturating instead of overflowing.
+/// Examples
+/// ```
+/// use abandoning_reorg::{MemoryStore, Organizer, ReorgNode, Weight};
+///
+/// /// Difficulty as a 256 bit number, the most significant word first.
+/// #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+/// struct Difficulty([u64; 4]);
+///
+/// impl Weight for Difficulty {
+///     type Sum = Difficulty;
+///
+///     fn zero() -> Difficulty {
+///         Difficulty::default()
+///     }
+///
+///     fn add(sum: &Difficulty, weight: &Difficulty) -> Difficulty {
+///         let mut words = [0; 4];
+///         let mut carry = false;
+///         for i in (0..4).rev() {
+///             let (word, first) = sum.0[i].overflowing_add(weight.0[i]);
+///             let (word, second) = word.overflowing_add(u64::from(carry));
+///             words[i] = word;
+///             carry = first || second;
+///         }
+///         Difficulty(words)
+///     }
+///
+///     fn sub(sum: &Difficulty, other: &Difficulty) -> Difficulty {
+///         let mut words = [0; 4];
+///         let mut borrow = false;
+///         for i in (0..4).rev() {
+///             let (word, first) = sum.0[i].overflowing_sub(other.0[i]);
+///             let (word, second) = word.overflowing_sub(u64::from(borrow));
+///             words[i] = word;
+///             borrow = first || second;
+///         }
+///         Difficulty(words)
+///     }
+/// }
+///
+/// let mut organizer =
+///     Organizer::<u64, (), MemoryStore<u64, ()>, Difficulty>::with_store(MemoryStore::default(), 777);
+/// organizer.set_value_based(true);
+/// organizer.init(ReorgNode::new(1, 0, Difficulty::default(), 0, ()));
+/// organizer.insert(ReorgNode::new(2, 1, Difficulty::default(), 1, ()), None);
+/// organizer.insert(ReorgNode::new(3, 1, Difficulty::default(), 1, ()), None);
+/// organizer.insert(ReorgNode::new(4, 2, Difficulty([0, 0, 0, u64::MAX]), 2, ()), None);
+/// organizer.insert(ReorgNode::new(5, 2, Difficulty([0, 0, 1, 0]), 3, ()), None);
+/// assert_eq!(organizer.canonical_head(), 5);
+/// assert_eq!(organizer.value_above_root(&5), Some(Difficulty([0, 0, 1, 0])));
+/// ```
+pub trait Weight: Clone + Debug + Default {
+    /// The sum of the weights of a lineage, compared to decide the leading branch.
+    type Sum: Clone + Debug + Ord;
+
+    /// Returns the sum of no weights.
+    fn zero() -> Self::Sum;
+
+    /// Returns the sum with the weight added.
+    fn add(sum: &Self::Sum, weight: &Self) -> Self::Sum;
+
+    /// Returns the sum with the other sum taken away, the other sum being the sum of
+    /// a part of its weights.
+    fn sub(sum: &Self::Sum, other: &Self::Sum) -> Self::Sum;
+}
+
+macro_rules! impl_weight {
+    ($($t:ty),*) => {
+        $(
+            impl Weight for $t {
+                type Sum = u128;
+
+                fn zero() -> u128 {
+                    0
+                }
+
+                fn add(sum: &u128, weight: &$t) -> u128 {
+                    sum.saturating_add(u128::from(*weight))
+                }
+
+                fn sub(sum: &u128, other: &u128) -> u128 {
+                    sum.saturating_sub(*other)
+                }
+            }
+        )*
+    };
+}
+
+impl_weight!(u8, u16, u32, u64, u128);
@@ -0,0 +1,5 @@
+//! Helpers shared by the integration tests and the benchmarks, which include this
+//! module by its path. Not every helper is used by every one of them.
+#![allow(dead_code)]
+
+pub mod workload;
@@ -0,0 +1,213 @@
+//! Deterministic generator of fork workloads for tests and benchmarks. The nodes of
+//! competing miners, late orphans, deep reorganizations and out of order delivery
+//! are produced from a seed, so that every run of a workload is the same.
+
+use std::collections::HashMap;
+
+use abandoning_reorg::ReorgNode;
+
+/// Small pseudo random number generator (SplitMix64), so that the workloads can be
+/// reproduced from their seeds without any dependency.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a generator from the seed.
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    /// Returns the next pseudo random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number below the bound, or zero if the bound is zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+
+    /// Returns true with the designated chance in percent.
+    pub fn chance(&mut self, percent: u8) -> bool {
+        self.below(100) < u64::from(percent)
+    }
+}
+
+/// Shape of a [`Workload`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorkloadConfig {
+    /// The number of nodes generated.
+    pub nodes: usize,
+    /// The chance in percent that a node competes with the head instead of extending it.
+    pub fork_percent: u8,
+    /// The chance in percent that a node is delivered late, after some of its descendants.
+    pub late_percent: u8,
+    /// The greatest number of nodes a late node is delivered after.
+    pub max_lateness: usize,
+    /// The chance in percent that a hidden branch is released, replacing the head.
+    pub reorg_percent: u8,
+    /// The greatest number of canonical nodes a hidden branch replaces.
+    pub max_reorg_depth: u64,
+    /// The number of nodes among which the delivery order is shuffled.
+    pub reorder_window: usize,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        WorkloadConfig {
+            nodes: 10_000,
+            fork_percent: 10,
+            late_percent: 2,
+            max_lateness: 20,
+            reorg_percent: 1,
+            max_reorg_depth: 12,
+            reorder_window: 4,
+        }
+    }
+}
+
+/// Iterator over the nodes of a generated fork workload, in the order of their delivery.
+/// The nodes have consecutive keys from one and empty meta data, and descend from
+/// [`Workload::genesis`].
+#[derive(Clone)]
+pub struct Workload {
+    config: WorkloadConfig,
+    rng: SplitMix64,
+    /// The parent and the height of every generated node by its key.
+    generated: HashMap<u64, (u64, u64)>,
+    /// The key and the height of the highest generated node, the first one seen.
+    head: (u64, u64),
+    /// The key of the next node.
+    next_key: u64,
+    /// Nodes held back until the number of generated nodes reaches the first field.
+    delayed: Vec<(usize, ReorgNode<u64, ()>)>,
+    /// Nodes ready to be delivered in a shuffled order.
+    window: Vec<ReorgNode<u64, ()>>,
+}
+
+impl Workload {
+    /// Creates the workload of the configuration, determined by the seed.
+    pub fn new(config: WorkloadConfig, seed: u64) -> Self {
+        let mut generated = HashMap::new();
+        generated.insert(0, (u64::MAX, 0));
+        Workload {
+            config,
+            rng: SplitMix64::new(seed),
+            generated,
+            head: (0, 0),
+            next_key: 1,
+            delayed: Vec::new(),
+            window: Vec::new(),
+        }
+    }
+
+    /// Returns the root every node of the workloads descends from.
+    pub fn genesis() -> ReorgNode<u64, ()> {
+        ReorgNode::new(0, 0, 0, u64::MAX, ())
+    }
+
+    /// Returns the number of nodes generated so far.
+    fn generated_count(&self) -> usize {
+        self.generated.len() - 1
+    }
+
+    /// Returns the ancestor of the head the designated number of nodes below it.
+    fn ancestor(&self, depth: u64) -> (u64, u64) {
+        let (mut key, mut height) = self.head;
+        for _ in 0..depth {
+            match self.generated.get(&key) {
+                Some((parent, _)) if *parent != u64::MAX => {
+                    key = *parent;
+                    height = self.generated[&key].1;
+                }
+                _ => break,
+            }
+        }
+        (key, height)
+    }
+
+    /// Generates a node on the parent, and queues it for delivery.
+    fn generate(&mut self, parent: (u64, u64)) -> (u64, u64) {
+        let key = self.next_key;
+        self.next_key += 1;
+        let height = parent.1 + 1;
+        self.generated.insert(key, (parent.0, height));
+        if height > self.head.1 {
+            self.head = (key, height);
+        }
+        let node = ReorgNode::new(key, height, self.rng.below(100), parent.0, ());
+        if self.rng.chance(self.config.late_percent) {
+            let lateness = self.rng.below(self.config.max_lateness as u64) as usize;
+            let release = self.generated_count() + 1 + lateness;
+            self.delayed.push((release, node));
+        } else {
+            self.window.push(node);
+        }
+        (key, height)
+    }
+
+    /// Generates the nodes of the next step: an extension of the head, a competing
+    /// node next to it, or a hidden branch overtaking it.
+    fn step(&mut self) {
+        let remaining = (self.config.nodes - self.generated_count()) as u64;
+        if self.rng.chance(self.config.reorg_percent) && remaining > 2 {
+            let depth = 1 + self
+                .rng
+                .below(self.config.max_reorg_depth.min(remaining - 2));
+            let mut parent = self.ancestor(depth);
+            for _ in 0..=depth {
+                parent = self.generate(parent);
+            }
+        } else if self.rng.chance(self.config.fork_percent) {
+            let depth = 1 + self.rng.below(2);
+            let parent = self.ancestor(depth);
+            self.generate(parent);
+        } else {
+            self.generate(self.head);
+        }
+        let count = self.generated_count();
+        let mut i = 0;
+        while i < self.delayed.len() {
+            if self.delayed[i].0 <= count {
+                let (_, node) = self.delayed.swap_remove(i);
+                self.window.push(node);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+impl Iterator for Workload {
+    type Item = ReorgNode<u64, ()>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.window.len() <= self.config.reorder_window {
+            if self.generated_count() < self.config.nodes {
+                self.step();
+            } else if !self.delayed.is_empty() {
+                // Once every node is generated, the late ones arrive in the order of their release
+                self.delayed.sort_by_key(|(release, _)| *release);
+                let (_, node) = self.delayed.remove(0);
+                self.window.push(node);
+            } else {
+                break;
+            }
+        }
+        if self.window.is_empty() {
+            return None;
+        }
+        let index = self.rng.below(self.window.len() as u64) as usize;
+        Some(self.window.remove(index))
+    }
+}
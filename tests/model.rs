@@ -0,0 +1,351 @@
+//! Compares the [`Organizer`] against a naive reference model, that keeps every node
+//! it has ever seen and computes the tree, the fork choice and the canonical head from
+//! scratch after every insert.
+
+mod common;
+
+use abandoning_reorg::{ForkChoice, InsertOutcome, Organizer, Rejection, ReorgNode, TieBreaker};
+use common::workload::{SplitMix64, Workload, WorkloadConfig};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// Node as remembered by the model.
+#[derive(Clone, Copy)]
+struct Entry {
+    height: u64,
+    value: u64,
+    parent: u64,
+    /// The sequence number of the arrival.
+    sequence: u64,
+}
+
+/// Reference implementation of the organizer, for rooted trees without limits or validators.
+struct Model {
+    allowed_depth: u64,
+    most_valuable: bool,
+    /// Every node ever attached, finalized and abandoned ones included.
+    attached: HashMap<u64, Entry>,
+    /// The children of every node, in the order they were attached.
+    children: HashMap<u64, Vec<u64>>,
+    /// The nodes waiting for their parents.
+    buffer: HashMap<u64, Entry>,
+    root: u64,
+    /// The nodes that became the root, the oldest first.
+    finalized: Vec<u64>,
+    /// The number of nodes taken in so far.
+    arrivals: u64,
+}
+
+impl Model {
+    fn new(root: &ReorgNode<u64, ()>, allowed_depth: u64, most_valuable: bool) -> Self {
+        let mut attached = HashMap::new();
+        attached.insert(
+            *root.key(),
+            Entry {
+                height: root.height(),
+                value: root.value(),
+                parent: *root.parent(),
+                sequence: 0,
+            },
+        );
+        Model {
+            allowed_depth,
+            most_valuable,
+            attached,
+            children: HashMap::new(),
+            buffer: HashMap::new(),
+            root: *root.key(),
+            finalized: Vec::new(),
+            arrivals: 0,
+        }
+    }
+
+    /// The nodes descending from the root, which is not included.
+    fn live(&self) -> HashSet<u64> {
+        let mut live = HashSet::new();
+        let mut stack = vec![self.root];
+        while let Some(key) = stack.pop() {
+            for child in self.children.get(&key).into_iter().flatten() {
+                live.insert(*child);
+                stack.push(*child);
+            }
+        }
+        live
+    }
+
+    fn height(&self) -> u64 {
+        let root = self.attached[&self.root].height;
+        self.live()
+            .iter()
+            .map(|key| self.attached[key].height)
+            .fold(root, u64::max)
+    }
+
+    fn allowed_oldest(&self) -> u64 {
+        self.height().saturating_sub(self.allowed_depth)
+    }
+
+    /// The child of the root the head descends from, and the worth of the lineage
+    /// from the head down to that child, the child not included.
+    fn head_worth(&self, head: u64) -> (u64, u64) {
+        let mut key = head;
+        let mut worth = 0;
+        while self.attached[&key].parent != self.root {
+            worth += if self.most_valuable {
+                self.attached[&key].value
+            } else {
+                1
+            };
+            key = self.attached[&key].parent;
+        }
+        (key, worth)
+    }
+
+    /// The sum of the values and the number of the nodes from the root up to the node,
+    /// the root not included.
+    fn above_root(&self, mut key: u64) -> (u128, u64) {
+        let (mut value, mut height) = (0, 0);
+        while key != self.root {
+            value += u128::from(self.attached[&key].value);
+            height += 1;
+            key = self.attached[&key].parent;
+        }
+        (value, height)
+    }
+
+    /// The nodes at the greatest height.
+    fn heads(&self) -> Vec<u64> {
+        let height = self.height();
+        self.live()
+            .into_iter()
+            .filter(|key| self.attached[key].height == height)
+            .collect()
+    }
+
+    /// The child of the root with the worthiest head, the greatest key winning ties.
+    fn leading_branch(&self) -> u64 {
+        let mut worth_by_branch: HashMap<u64, u64> = HashMap::new();
+        for head in self.heads() {
+            let (branch, worth) = self.head_worth(head);
+            let greatest = worth_by_branch.entry(branch).or_insert(worth);
+            *greatest = (*greatest).max(worth);
+        }
+        worth_by_branch
+            .into_iter()
+            .max_by_key(|(branch, worth)| (*worth, *branch))
+            .map_or(self.root, |(branch, _)| branch)
+    }
+
+    /// The worthiest head of the leading branch, the first arrived one winning ties.
+    fn canonical_head(&self) -> u64 {
+        let branch = self.leading_branch();
+        if branch == self.root {
+            return self.root;
+        }
+        self.heads()
+            .into_iter()
+            .map(|head| (head, self.head_worth(head)))
+            .filter(|(_, (root, _))| *root == branch)
+            .max_by_key(|(head, (_, worth))| (*worth, Reverse(self.attached[head].sequence)))
+            .unwrap()
+            .0
+    }
+
+    fn insert(&mut self, node: &ReorgNode<u64, ()>) -> InsertOutcome<u64> {
+        let mut entry = Entry {
+            height: node.height(),
+            value: node.value(),
+            parent: *node.parent(),
+            sequence: 0,
+        };
+        let live = self.live();
+        let parent_known = live.contains(&entry.parent) || entry.parent == self.root;
+        if entry.height <= self.allowed_oldest() {
+            return InsertOutcome::Rejected(Rejection::TooOld);
+        }
+        let root_height = self.attached[&self.root].height;
+        if entry.height <= root_height || (!parent_known && entry.height == root_height + 1) {
+            return InsertOutcome::Rejected(Rejection::Conflicting);
+        }
+        self.arrivals += 1;
+        entry.sequence = self.arrivals;
+        if !parent_known {
+            self.buffer.insert(*node.key(), entry);
+            return InsertOutcome::Buffered;
+        }
+        self.attach(*node.key(), entry);
+        // Buffered nodes attach once their parents are in the tree
+        loop {
+            let live = self.live();
+            let ready: Vec<u64> = self
+                .buffer
+                .iter()
+                .filter(|(_, entry)| live.contains(&entry.parent))
+                .map(|(key, _)| *key)
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            for key in ready {
+                let entry = self.buffer.remove(&key).unwrap();
+                self.attach(key, entry);
+            }
+        }
+        let mut finalized = Vec::new();
+        while self.attached[&self.root].height < self.allowed_oldest()
+            && self
+                .children
+                .get(&self.root)
+                .is_some_and(|children| !children.is_empty())
+        {
+            self.root = self.leading_branch();
+            finalized.push(self.root);
+        }
+        self.finalized.extend(&finalized);
+        let oldest = self.allowed_oldest();
+        self.buffer.retain(|_, entry| entry.height >= oldest);
+        InsertOutcome::Attached { finalized }
+    }
+
+    fn attach(&mut self, key: u64, entry: Entry) {
+        self.attached.insert(key, entry);
+        self.children.entry(entry.parent).or_default().push(key);
+    }
+}
+
+/// Inserts the nodes into the organizer and the model alike, comparing them after every step.
+fn compare<I>(nodes: I, allowed_depth: u64, fork_choice: ForkChoice)
+where
+    I: IntoIterator<Item = ReorgNode<u64, ()>>,
+{
+    let genesis = Workload::genesis();
+    let mut model = Model::new(
+        &genesis,
+        allowed_depth,
+        fork_choice == ForkChoice::MostValuable,
+    );
+    let mut organizer = Organizer::builder()
+        .depth(allowed_depth)
+        .fork_choice(fork_choice)
+        .tie_breaker(TieBreaker::Custom(|a: &u64, b: &u64| a.cmp(b)))
+        .root(genesis)
+        .build()
+        .unwrap();
+    let mut finalized = Vec::new();
+    for node in nodes {
+        let key = *node.key();
+        let expected = model.insert(&node);
+        let outcome = organizer.insert(node, None);
+        assert_eq!(outcome, expected, "outcome of {}", key);
+        if let InsertOutcome::Attached { finalized: keys } = outcome {
+            finalized.extend(keys);
+        }
+        assert_eq!(finalized, model.finalized);
+        assert_eq!(*organizer.root().key(), model.root);
+        assert_eq!(organizer.height(), model.height());
+        let head = model.canonical_head();
+        assert_eq!(
+            organizer.canonical_head(),
+            head,
+            "canonical head after {}",
+            key
+        );
+        let (value, height) = model.above_root(head);
+        assert_eq!(organizer.value_above_root(&head), Some(value));
+        assert_eq!(organizer.height_above_root(&head), Some(height));
+        // The tree is the same as the live part of the model, and indexed consistently
+        let live = model.live();
+        let stats = organizer.stats();
+        assert_eq!(stats.nodes, live.len() + 1);
+        assert_eq!(stats.buffered, model.buffer.len());
+        for key in live.iter().chain(std::iter::once(&model.root)) {
+            let node = organizer.get(key).unwrap();
+            if *key != model.root {
+                assert_eq!(*node.parent(), model.attached[key].parent);
+            }
+            let mut children = node.children().to_vec();
+            children.sort_unstable();
+            let mut expected = model.children.get(key).cloned().unwrap_or_default();
+            expected.retain(|child| live.contains(child));
+            expected.sort_unstable();
+            assert_eq!(children, expected);
+        }
+        assert_eq!(organizer.check_height_to_key_diff(), vec![model.root]);
+        let chain = organizer.canonical_chain();
+        assert_eq!(chain[0], model.root);
+        assert!(chain
+            .windows(2)
+            .all(|pair| organizer.get(&pair[1]).unwrap().parent() == &pair[0]));
+    }
+}
+
+/// Gives the nodes values spread widely enough that lineages are rarely equally valuable.
+fn revalue(nodes: Workload, seed: u64) -> impl Iterator<Item = ReorgNode<u64, ()>> {
+    let mut rng = SplitMix64::new(seed);
+    nodes.map(move |node| {
+        ReorgNode::new(
+            *node.key(),
+            node.height(),
+            rng.below(1 << 20),
+            *node.parent(),
+            (),
+        )
+    })
+}
+
+#[test]
+fn model_longest_test() {
+    let config = WorkloadConfig {
+        nodes: 600,
+        fork_percent: 25,
+        reorg_percent: 3,
+        ..WorkloadConfig::default()
+    };
+    for seed in 0..6 {
+        for allowed_depth in [4, 16] {
+            compare(
+                Workload::new(config, seed),
+                allowed_depth,
+                ForkChoice::Longest,
+            );
+        }
+    }
+}
+
+#[test]
+fn model_most_valuable_test() {
+    let config = WorkloadConfig {
+        nodes: 600,
+        fork_percent: 25,
+        reorg_percent: 3,
+        ..WorkloadConfig::default()
+    };
+    for seed in 0..6 {
+        for allowed_depth in [4, 16] {
+            compare(
+                revalue(Workload::new(config, seed), seed),
+                allowed_depth,
+                ForkChoice::MostValuable,
+            );
+        }
+    }
+}
+
+#[test]
+fn model_shuffled_test() {
+    // The whole workload delivered in a random order, so that most nodes are orphans
+    let config = WorkloadConfig {
+        nodes: 300,
+        reorder_window: 0,
+        ..WorkloadConfig::default()
+    };
+    for seed in 0..6 {
+        let mut nodes: Vec<ReorgNode<u64, ()>> = Workload::new(config, seed).collect();
+        let mut rng = SplitMix64::new(seed);
+        for i in (1..nodes.len()).rev() {
+            nodes.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+        compare(nodes.clone(), 8, ForkChoice::Longest);
+        compare(nodes, 64, ForkChoice::Longest);
+    }
+}
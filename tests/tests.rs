@@ -1,4 +1,14 @@
-use abandoning_reorg::{Organizer, ReorgNode};
+mod common;
+
+use abandoning_reorg::{
+    Actor, BuildError, Codec, Event, FileStore, ForkChoice, InsertOutcome, JournalError,
+    MemoryStore, Misbehavior, Observer, Organizer, Rejection, ReorgNode, SharedOrganizer,
+    SnapshotError, Stopped, TieBreaker, Weight, DEFAULT_BUFFER_LIMIT, DEFAULT_MAX_ORPHAN_DISTANCE,
+};
+use common::workload::{SplitMix64, Workload, WorkloadConfig};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 /// Utility function that creates a key([u8;32]) from a u64
 fn utoa(u: u64) -> [u8; 32] {
@@ -14,7 +24,7 @@ fn create_test_filled() -> Organizer<[u8; 32], ()> {
     let mut cb = Organizer::new(255, false);
     cb.init(genesis);
     for i in 1..2000 {
-        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None)
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
     }
     cb
 }
@@ -48,7 +58,7 @@ fn insert_test() {
     cb.init(genesis);
     println!("\npost init state \n{}", cb);
     for i in 1..2000 {
-        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None)
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
     }
 }
 
@@ -61,7 +71,7 @@ fn callback_test() {
     cb.init(genesis);
     println!("\npost init state \n{}", cb);
     for i in 1..2000 {
-        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None)
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
     }
 }
 
@@ -73,7 +83,6 @@ fn fail_test() {
 
 #[test]
 fn test() {
-    // Test intentionally fails
     let genesis = ReorgNode::new(utoa(0), 0, 0, utoa(999999999), ());
     println!("genesis: \n{}", genesis);
     let mut cb = Organizer::new(255, false);
@@ -81,7 +90,7 @@ fn test() {
     cb.init(genesis);
     println!("\npost init state \n{}", cb);
     for i in 1..2000 {
-        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None)
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
     }
     println!("\ntree before pushing extra branches \n{}", cb);
     for i in 0..10 {
@@ -91,6 +100,7 @@ fn test() {
         );
     }
     println!("\ntree after pushing extra branches \n{}", cb);
+    assert_eq!(cb.highest_nodes(), &[utoa(1999)]);
     for i in 0..1000 {
         cb.insert(
             ReorgNode::new(utoa(2010 + i), 1997 + i, 0, utoa(2009 + i), ()),
@@ -99,15 +109,1344 @@ fn test() {
     }
     println!("\ntree after continuing one of the branches \n{}", cb);
     println!("-----------");
-    println!(
-        "This should be the same as the root and nothing else\n{:?}",
-        cb.check_height_to_key_diff()
+    // Only the continued branch survives, the root following it
+    assert_eq!(cb.check_height_to_key_diff(), vec![*cb.root().key()]);
+    assert_eq!(cb.highest_nodes(), &[utoa(3009)]);
+    assert_eq!(cb.height(), 2996);
+    assert_eq!(cb.root().height(), 2996 - 255);
+    for i in 0..10 {
+        assert!(cb.get(&utoa(2000 + i)).is_none());
+    }
+    let mut visited = Vec::new();
+    cb.apply_callback(
+        Some(utoa(3009)),
+        Some(utoa(3000)),
+        &mut |node: &ReorgNode<[u8; 32], ()>| visited.push(*node.key()),
     );
-    println!("Highest node(s): {:?}", cb.highest_nodes());
+    assert_eq!(visited, (3001..3010).rev().map(utoa).collect::<Vec<_>>());
     cb.apply_callback(Some(utoa(3009)), Some(utoa(3000)), &mut callback);
     cb.list_nodes();
     println!("deleting branch");
-    cb.delete_children(&utoa(2850));
+    let deleted = cb.delete_children(&utoa(2850));
+    assert_eq!(deleted.len(), 160);
+    assert_eq!(cb.highest_nodes(), &[utoa(2849)]);
+    assert_eq!(cb.height(), 2836);
+    assert_eq!(cb.check_height_to_key_diff(), vec![*cb.root().key()]);
+    cb.list_nodes();
+}
+
+#[test]
+fn finalize_test() {
+    let mut cb = create_test_filled();
+    // Two competing branches stemming from 1995
+    cb.insert(ReorgNode::new(utoa(3000), 1996, 0, utoa(1995), ()), None);
+    cb.insert(ReorgNode::new(utoa(3001), 1997, 0, utoa(3000), ()), None);
+    let abandoned = cb.finalize(&utoa(1997)).unwrap();
+    assert_eq!(abandoned.len(), 2);
+    assert_eq!(cb.root().key(), &utoa(1997));
+    assert!(cb.get(&utoa(3000)).is_none());
+    assert!(cb.get(&utoa(1996)).is_none());
+    assert_eq!(cb.height(), 1999);
+    assert_eq!(cb.check_height_to_key_diff(), vec![utoa(1997)]);
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(3002), 1997, 0, utoa(1996), ()), None),
+        InsertOutcome::Rejected(Rejection::Conflicting)
+    );
+    // Children of the abandoned branches conflict with the finalized node as well
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(3002), 1998, 0, utoa(3000), ()), None),
+        InsertOutcome::Rejected(Rejection::Conflicting)
+    );
+    assert_eq!(cb.stats().buffered, 0);
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(2000), 2000, 0, utoa(1999), ()), None),
+        InsertOutcome::Attached { finalized: vec![] }
+    );
+    assert!(cb.finalize(&utoa(3000)).is_none());
+}
+
+#[test]
+fn rewind_test() {
+    let mut cb = create_test_filled();
+    cb.insert(ReorgNode::new(utoa(3000), 1998, 0, utoa(1997), ()), None);
+    let popped = cb.pop_head();
+    assert_eq!(popped.len(), 1);
+    assert_eq!(popped[0].key(), &utoa(1999));
+    assert_eq!(cb.height(), 1998);
+    let rewound = cb.rewind_to(1990);
+    assert_eq!(rewound.len(), 9);
+    assert_eq!(cb.height(), 1990);
+    assert!(cb.get(&utoa(1990)).unwrap().children().is_empty());
+    assert_eq!(cb.check_height_to_key_diff(), vec![*cb.root().key()]);
+    for node in rewound.into_iter().rev() {
+        assert!(matches!(
+            cb.insert(node, None),
+            InsertOutcome::Attached { .. }
+        ));
+    }
+    assert_eq!(cb.height(), 1998);
+    assert_eq!(cb.get(&utoa(1991)).unwrap().children(), &[utoa(1992)]);
+    let root_height = cb.root().height();
+    cb.rewind_to(0);
+    assert_eq!(cb.height(), root_height);
+    assert!(cb.pop_head().is_empty());
+}
+
+#[test]
+fn forest_test() {
+    let mut cb = Organizer::<[u8; 32], ()>::new_forest(50, false);
+    // Two chains without a common root, one of them delivered child first
+    cb.insert(ReorgNode::new(utoa(102), 102, 0, utoa(101), ()), None);
+    cb.insert(ReorgNode::new(utoa(1100), 100, 0, utoa(1099), ()), None);
+    cb.insert(ReorgNode::new(utoa(101), 101, 0, utoa(100), ()), None);
+    assert_eq!(cb.candidate_roots().len(), 2);
+    assert!(cb.candidate_roots().contains(&utoa(101)));
+    assert_eq!(cb.get(&utoa(102)).unwrap().parent(), &utoa(101));
+    for i in 101..140 {
+        cb.insert(
+            ReorgNode::new(utoa(1000 + i), i, 0, utoa(999 + i), ()),
+            None,
+        );
+    }
+    for i in 103..151 {
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
+    }
+    assert!(!cb.is_forest());
+    assert_eq!(cb.root().parent(), &utoa(100));
+    // The branch delivered child first accumulates on top of its real ancestors
+    assert_eq!(
+        cb.height_above_root(&utoa(150)),
+        Some(150 - cb.root().height())
+    );
+    assert_eq!(
+        cb.get(&utoa(102)).unwrap().cumulative_height(),
+        cb.get(&utoa(101)).unwrap().cumulative_height() + 1
+    );
+    assert!(cb.get(&utoa(1101)).is_none());
+    assert_eq!(cb.candidate_roots(), &[*cb.root().key()]);
+
+    // Rejected nodes leave the placeholder root where it is
+    let mut cb = Organizer::builder()
+        .forest()
+        .validator(|node: &ReorgNode<[u8; 32], ()>| node.value() < 100)
+        .build()
+        .unwrap();
+    cb.insert(ReorgNode::new(utoa(50), 50, 0, utoa(49), ()), None);
+    assert_eq!(cb.root().height(), 49);
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(10), 10, 100, utoa(9), ()), None),
+        InsertOutcome::Rejected(Rejection::Invalid)
+    );
+    assert_eq!(cb.root().height(), 49);
+    assert_eq!(cb.height(), 50);
+    cb.insert(ReorgNode::new(utoa(10), 10, 0, utoa(9), ()), None);
+    assert_eq!(cb.root().height(), 9);
+}
+
+#[test]
+fn default_root_test() {
+    // The default root leads the tree until the first node is attached to it
+    let mut cb = Organizer::<u64, ()>::default();
+    assert_eq!(cb.highest_nodes(), &[0]);
+    assert_eq!(cb.canonical_head(), 0);
+    assert_eq!(
+        cb.insert(ReorgNode::new(1, 1, 0, 0, ()), None),
+        InsertOutcome::Attached { finalized: vec![] }
+    );
+    assert_eq!(cb.canonical_head(), 1);
+    let shared = SharedOrganizer::new(Organizer::<u64, ()>::new(10, false));
+    shared.insert(ReorgNode::new(1, 1, 0, 0, ()), None);
+    assert_eq!(shared.chain().keys(), &[0, 1]);
+}
+
+#[test]
+fn height_jump_test() {
+    let genesis = ReorgNode::new(utoa(0), 0, 0, utoa(999999999), ());
+    let mut cb = Organizer::new(10, false);
+    cb.init(genesis);
+    for i in 1..6 {
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
+    }
+    // A competing branch that will lose
+    cb.insert(ReorgNode::new(utoa(100), 3, 0, utoa(2), ()), None);
+    // A long chain arriving before its first node
+    for i in 7..31 {
+        assert_eq!(
+            cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None),
+            InsertOutcome::Buffered
+        );
+    }
+    let outcome = cb.insert(ReorgNode::new(utoa(6), 6, 0, utoa(5), ()), None);
+    assert_eq!(
+        outcome,
+        InsertOutcome::Attached {
+            finalized: (1..21).map(utoa).collect()
+        }
+    );
+    assert_eq!(cb.height(), 30);
+    assert_eq!(cb.root().key(), &utoa(20));
+    assert!(cb.get(&utoa(100)).is_none());
+    assert_eq!(cb.check_height_to_key_diff(), vec![utoa(20)]);
+}
+
+/// Observer counting the finalized and abandoned nodes.
+struct Counter(Arc<AtomicUsize>, Arc<AtomicUsize>);
+
+impl Observer<[u8; 32], ()> for Counter {
+    fn finalized(&mut self, _node: &ReorgNode<[u8; 32], ()>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn abandoned(&mut self, _node: &ReorgNode<[u8; 32], ()>) {
+        self.1.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn builder_test() {
+    let genesis = ReorgNode::new(utoa(0), 0, 0, utoa(999999999), ());
+    assert_eq!(
+        Organizer::<[u8; 32], ()>::builder().build().err(),
+        Some(BuildError::MissingRoot)
+    );
+    assert_eq!(
+        Organizer::builder()
+            .depth(0)
+            .root(genesis.clone())
+            .build()
+            .err(),
+        Some(BuildError::ZeroDepth)
+    );
+    assert_eq!(
+        Organizer::builder()
+            .forest()
+            .root(genesis.clone())
+            .build()
+            .err(),
+        Some(BuildError::RootInForest)
+    );
+    let (finalized, abandoned) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let mut cb = Organizer::builder()
+        .depth(10)
+        .fork_choice(ForkChoice::MostValuable)
+        .tie_breaker(TieBreaker::LastSeen)
+        .buffer_limit(1)
+        .validator(|node: &ReorgNode<[u8; 32], ()>| node.value() < 100)
+        .observer(Counter(finalized.clone(), abandoned.clone()))
+        .root(genesis)
+        .build()
+        .unwrap();
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(1), 1, 100, utoa(0), ()), None),
+        InsertOutcome::Rejected(Rejection::Invalid)
+    );
+    // Two equally valuable branches, the last seen one wins
+    cb.insert(ReorgNode::new(utoa(1), 1, 1, utoa(0), ()), None);
+    cb.insert(ReorgNode::new(utoa(101), 1, 1, utoa(0), ()), None);
+    for i in 2..11 {
+        cb.insert(ReorgNode::new(utoa(i), i, 1, utoa(i - 1), ()), None);
+        cb.insert(ReorgNode::new(utoa(100 + i), i, 1, utoa(99 + i), ()), None);
+    }
+    assert_eq!(cb.find_longest_branch(None), utoa(101));
+    cb.insert(ReorgNode::new(utoa(111), 11, 1, utoa(110), ()), None);
+    assert_eq!(cb.root().key(), &utoa(101));
+    assert_eq!(finalized.load(Ordering::SeqCst), 1);
+    assert_eq!(abandoned.load(Ordering::SeqCst), 10);
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(20), 20, 1, utoa(19), ()), None),
+        InsertOutcome::Buffered
+    );
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(21), 21, 1, utoa(20), ()), None),
+        InsertOutcome::Rejected(Rejection::BufferFull)
+    );
+    // Without configuration the orphans are still limited
+    let mut cb = create_test_filled();
+    let far = cb.height() + DEFAULT_MAX_ORPHAN_DISTANCE;
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(5000), far + 1, 1, utoa(4999), ()), None),
+        InsertOutcome::Rejected(Rejection::TooFarAhead)
+    );
+    for i in 0..DEFAULT_BUFFER_LIMIT as u64 {
+        cb.insert(
+            ReorgNode::new(utoa(6000 + i), far, 1, utoa(8000 + i), ()),
+            None,
+        );
+    }
+    assert_eq!(
+        cb.insert(ReorgNode::new(utoa(5000), far, 1, utoa(4999), ()), None),
+        InsertOutcome::Rejected(Rejection::BufferFull)
+    );
+}
+
+#[test]
+fn snapshot_test() {
+    let mut cb = create_test_filled();
+    cb.set_value_based(true);
+    cb.insert(ReorgNode::new(utoa(3000), 1999, 0, utoa(1998), ()), None);
+    cb.insert(ReorgNode::new(utoa(3002), 2002, 0, utoa(3001), ()), None);
+    let snapshot = cb.snapshot();
+    let mut restored = Organizer::<[u8; 32], ()>::restore(&snapshot).unwrap();
+    assert_eq!(restored.snapshot().len(), snapshot.len());
+    assert_eq!(restored.root().key(), cb.root().key());
+    assert_eq!(restored.height(), 1999);
+    assert_eq!(restored.fork_choice(), ForkChoice::MostValuable);
+    assert_eq!(restored.highest_nodes().len(), 2);
+    assert_eq!(
+        restored.get(&utoa(1999)).unwrap().cumulative_height(),
+        cb.get(&utoa(1999)).unwrap().cumulative_height()
+    );
+    assert_eq!(
+        restored.check_height_to_key_diff(),
+        vec![*restored.root().key()]
+    );
+    // The buffered node is still waiting for its parent
+    restored.insert(ReorgNode::new(utoa(3001), 2000, 0, utoa(3000), ()), None);
+    assert_eq!(restored.height(), 2002);
+    assert_eq!(
+        Organizer::<[u8; 32], ()>::restore(&snapshot[..snapshot.len() - 1]).err(),
+        Some(SnapshotError::Truncated)
+    );
+    assert_eq!(
+        Organizer::<[u8; 32], ()>::restore(b"nothing").err(),
+        Some(SnapshotError::UnknownFormat)
+    );
+}
+
+/// Encodes a snapshot of the given nodes, the first one being the root, with the
+/// given height index, so that the structure can be broken on purpose.
+fn raw_snapshot(
+    height: u64,
+    nodes: &[(u64, u64, u64, &[u64])],
+    index: &[(u64, &[u64])],
+) -> Vec<u8> {
+    let encode_node = |node: &(u64, u64, u64, &[u64]), output: &mut Vec<u8>| {
+        let (key, height, parent, children) = *node;
+        key.encode(output);
+        height.encode(output);
+        1u64.encode(output);
+        parent.encode(output);
+        (children.len() as u64).encode(output);
+        for child in children {
+            child.encode(output);
+        }
+        key.encode(output);
+        None::<u64>.encode(output);
+        None::<u64>.encode(output);
+        u128::from(height).encode(output);
+        height.encode(output);
+    };
+    let mut output = b"ABRG".to_vec();
+    1u8.encode(&mut output);
+    10u64.encode(&mut output);
+    height.encode(&mut output);
+    ForkChoice::Longest.encode(&mut output);
+    false.encode(&mut output);
+    false.encode(&mut output);
+    12u64.encode(&mut output);
+    encode_node(&nodes[0], &mut output);
+    ((nodes.len() - 1) as u64).encode(&mut output);
+    for node in &nodes[1..] {
+        encode_node(node, &mut output);
+    }
+    (index.len() as u64).encode(&mut output);
+    for (height, keys) in index {
+        height.encode(&mut output);
+        (keys.len() as u64).encode(&mut output);
+        for key in keys.iter() {
+            key.encode(&mut output);
+        }
+    }
+    0u64.encode(&mut output);
+    0u64.encode(&mut output);
+    0u64.encode(&mut output);
+    output
+}
+
+#[test]
+fn malformed_snapshot_test() {
+    let nodes: [(u64, u64, u64, &[u64]); 5] = [
+        (0, 0, 0, &[1]),
+        (1, 1, 0, &[2, 12]),
+        (2, 2, 1, &[3]),
+        (3, 3, 2, &[]),
+        (12, 2, 1, &[]),
+    ];
+    let index: [(u64, &[u64]); 4] = [(0, &[0]), (1, &[1]), (2, &[2, 12]), (3, &[3])];
+    let restore = |height, nodes: &[(u64, u64, u64, &[u64])], index: &[(u64, &[u64])]| {
+        Organizer::<u64, ()>::restore(&raw_snapshot(height, nodes, index)).err()
+    };
+    let restored = Organizer::<u64, ()>::restore(&raw_snapshot(3, &nodes, &index)).unwrap();
+    assert_eq!(restored.highest_nodes(), vec![3]);
+    // An indexed key that is not stored
+    let index_missing: [(u64, &[u64]); 4] = [(0, &[0]), (1, &[1]), (2, &[2, 12]), (3, &[3, 4])];
+    assert_eq!(
+        restore(3, &nodes, &index_missing),
+        Some(SnapshotError::Malformed)
+    );
+    // An indexed key stored at another height
+    let index_wrong: [(u64, &[u64]); 4] = [(0, &[0]), (1, &[1]), (2, &[2]), (3, &[3, 12])];
+    assert_eq!(
+        restore(3, &nodes, &index_wrong),
+        Some(SnapshotError::Malformed)
+    );
+    // The root is not indexed
+    assert_eq!(
+        restore(3, &nodes, &index[1..]),
+        Some(SnapshotError::Malformed)
+    );
+    // The height is not indexed
+    assert_eq!(restore(4, &nodes, &index), Some(SnapshotError::Malformed));
+    // A child that is not stored
+    let mut orphaned = nodes;
+    orphaned[3] = (3, 3, 2, &[5]);
+    assert_eq!(
+        restore(3, &orphaned, &index),
+        Some(SnapshotError::Malformed)
+    );
+    // A child whose parent is another node
+    let mut adopted = nodes;
+    adopted[4] = (12, 2, 0, &[]);
+    assert_eq!(restore(3, &adopted, &index), Some(SnapshotError::Malformed));
+    // A parent that does not know its child
+    let mut forgotten = nodes;
+    forgotten[1] = (1, 1, 0, &[2]);
+    assert_eq!(
+        restore(3, &forgotten, &index),
+        Some(SnapshotError::Malformed)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_test() {
+    let mut cb = create_test_filled();
+    cb.insert(ReorgNode::new(utoa(3001), 2001, 0, utoa(3000), ()), None);
+    cb.insert_from(
+        ReorgNode::new(utoa(3001), 2001, 0, utoa(3000), ()),
+        Some(7),
+        None,
+        None,
+    );
+    let json = serde_json::to_string(&cb).unwrap();
+    let mut restored: Organizer<[u8; 32], ()> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.root().key(), cb.root().key());
+    assert_eq!(restored.misbehavior(7).duplicate, 1);
+    assert_eq!(restored.height(), cb.height());
+    assert_eq!(
+        restored.check_height_to_key_diff(),
+        vec![*restored.root().key()]
+    );
+    restored.insert(ReorgNode::new(utoa(3000), 2000, 0, utoa(1999), ()), None);
+    assert_eq!(restored.height(), 2001);
+    let node: ReorgNode<[u8; 32], ()> =
+        serde_json::from_str(&serde_json::to_string(&restored.root()).unwrap()).unwrap();
+    assert_eq!(node.key(), restored.root().key());
+}
+
+/// Journal sink that can be read back while the organizer still owns it.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn journal_test() {
+    let genesis = ReorgNode::new(utoa(0), 0, 0, utoa(999999999), ());
+    let mut cb = Organizer::new_with(genesis, 10, false);
+    for i in 1..5 {
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
+    }
+    // The tallies kept before the journal started are part of its snapshot
+    cb.insert_from(
+        ReorgNode::new(utoa(4), 4, 0, utoa(3), ()),
+        Some(7),
+        None,
+        None,
+    );
+    let journal = SharedBuffer::default();
+    cb.start_journal(journal.clone()).unwrap();
+    for i in 5..30 {
+        cb.insert(ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()), None);
+        cb.insert(
+            ReorgNode::new(utoa(100 + i), i, 1, utoa(i - 1), ()),
+            Some(true),
+        );
+    }
+    cb.insert(ReorgNode::new(utoa(40), 40, 0, utoa(39), ()), None);
+    cb.insert_from(
+        ReorgNode::new(utoa(3), 3, 0, utoa(2), ()),
+        Some(7),
+        None,
+        None,
+    );
+    cb.insert_from(
+        ReorgNode::new(utoa(28), 28, 0, utoa(27), ()),
+        Some(8),
+        None,
+        None,
+    );
+    cb.forgive(8);
+    cb.finalize(&utoa(22));
+    cb.delete_children(&utoa(125));
+    cb.pop_head();
+    cb.sync_journal().unwrap();
+    let bytes = journal.0.lock().unwrap().clone();
+    let (replayed, len) = Organizer::<[u8; 32], ()>::replay(bytes.as_slice()).unwrap();
+    assert_eq!(len, bytes.len() as u64);
+    assert_eq!(replayed.root().key(), &utoa(22));
+    assert_eq!(replayed.height(), cb.height());
+    assert_eq!(replayed.highest_nodes(), cb.highest_nodes());
+    assert!(replayed.get(&utoa(125)).is_none());
+    assert!(replayed.get(&utoa(124)).is_some());
+    assert_eq!(replayed.misbehavior(7), cb.misbehavior(7));
+    assert_eq!(replayed.misbehavior(7).total(), 2);
+    assert_eq!(replayed.misbehavior(8).total(), 0);
+    // Unlike the tallies, the counters start over at the snapshot
+    assert_eq!(replayed.stats().rejected(Rejection::Duplicate), 1);
+    assert_eq!(replayed.stats().rejected(Rejection::TooOld), 1);
+    assert_eq!(cb.stats().rejected(Rejection::Duplicate), 2);
+    // A torn final record is left out
+    let (torn, len) = Organizer::<[u8; 32], ()>::replay(&bytes[..bytes.len() - 3]).unwrap();
+    assert!(len < bytes.len() as u64 - 3);
+    assert_eq!(torn.height(), 29);
+    // A damaged record in the middle is refused
+    let mut damaged = bytes.clone();
+    let middle = damaged.len() / 2;
+    damaged[middle] ^= 0xff;
+    assert!(matches!(
+        Organizer::<[u8; 32], ()>::replay(damaged.as_slice()),
+        Err(JournalError::Corrupted(_))
+    ));
+    // The records are framed by their length, its checksum, the body and its checksum
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let len = bytes[offset..offset + 4]
+            .iter()
+            .rev()
+            .fold(0, |len, byte| len << 8 | usize::from(*byte));
+        records.push(offset);
+        offset += len + 12;
+    }
+    // A damaged length in the middle is not taken for a torn record
+    let mut damaged = bytes.clone();
+    damaged[records[6] + 1] ^= 0x01;
+    assert!(matches!(
+        Organizer::<[u8; 32], ()>::replay(damaged.as_slice()),
+        Err(JournalError::Corrupted(offset)) if offset == records[6] as u64
+    ));
+}
+
+#[test]
+fn journal_workload_test() {
+    let config = WorkloadConfig {
+        nodes: 2_000,
+        ..WorkloadConfig::default()
+    };
+    for seed in 0..4 {
+        let mut rng = SplitMix64::new(seed);
+        let mut cb = Organizer::<u64, ()>::new_with(Workload::genesis(), 16, false);
+        let journal = SharedBuffer::default();
+        cb.start_journal(journal.clone()).unwrap();
+        for node in Workload::new(config, seed) {
+            let source = Some(rng.below(4));
+            // Some nodes are delivered twice, so that the sources are blamed
+            if rng.chance(5) {
+                cb.insert_from(node.clone(), source, None, None);
+            }
+            cb.insert_from(node, source, None, None);
+            if rng.chance(1) {
+                cb.pop_head();
+            }
+            if rng.chance(1) {
+                cb.forgive(rng.below(4));
+            }
+        }
+        cb.sync_journal().unwrap();
+        let bytes = journal.0.lock().unwrap().clone();
+        let (replayed, _) = Organizer::<u64, ()>::replay(bytes.as_slice()).unwrap();
+        assert_eq!(replayed.root().key(), cb.root().key());
+        assert_eq!(replayed.canonical_chain(), cb.canonical_chain());
+        assert_eq!(replayed.stats(), cb.stats());
+        for source in 0..4 {
+            assert_eq!(replayed.misbehavior(source), cb.misbehavior(source));
+        }
+    }
+}
+
+#[test]
+fn store_test() {
+    let directory =
+        std::env::temp_dir().join(format!("abandoning_reorg_store_{}", std::process::id()));
+    let store = FileStore::<u64, u64>::new(&directory).unwrap();
+    let mut cb = Organizer::builder()
+        .depth(5)
+        .root(ReorgNode::new(0, 0, 0, 0, 1000))
+        .store(store)
+        .build()
+        .unwrap();
+    for i in 1..10 {
+        cb.insert(ReorgNode::new(i, i, 0, i - 1, 1000 + i), None);
+    }
+    cb.insert(ReorgNode::new(105, 5, 0, 4, 1105), None);
+    // Nodes waiting in the buffer keep their bodies in the store as well
+    cb.insert(ReorgNode::new(12, 12, 0, 11, 1012), None);
+    assert_eq!(cb.meta(&4).as_deref(), Some(&1004));
+    assert_eq!(cb.get(&9).unwrap().meta(), &1009);
+    assert!(cb.meta(&3).is_none());
+    let mut metas = Vec::new();
+    cb.apply_callback(Some(9), Some(6), &mut |node| metas.push(*node.meta()));
+    assert_eq!(metas, vec![1009, 1008, 1007]);
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 8);
+    let abandoned = cb.finalize(&6).unwrap();
+    assert_eq!(abandoned.len(), 1);
+    assert_eq!(abandoned[0].meta(), &1105);
+    let rewound = cb.rewind_to(7);
+    assert_eq!(
+        rewound.iter().map(|node| *node.meta()).collect::<Vec<_>>(),
+        vec![1009, 1008]
+    );
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 3);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    // Meta data that cannot be cloned is lent from memory to the callbacks
+    #[derive(Debug, Default)]
+    struct Heavy(Vec<u8>);
+    let mut cb = Organizer::<u64, Heavy>::new(5, false);
+    cb.init(ReorgNode::new(0, 0, 0, 0, Heavy(vec![0])));
+    cb.insert(ReorgNode::new(1, 1, 0, 0, Heavy(vec![1; 3])), None);
+    let mut sizes = Vec::new();
+    cb.apply_topology_callback(Some(1), None, &mut |_, meta| sizes.push(meta().0.len()));
+    assert_eq!(sizes, vec![3]);
+    assert_eq!(cb.meta(&0).unwrap().0, vec![0]);
+}
+
+#[test]
+fn shared_test() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedOrganizer<[u8; 32], ()>>();
+
+    let genesis = ReorgNode::new(0, 0, 0, 0, ());
+    let shared = Arc::new(SharedOrganizer::new(Organizer::<u64, ()>::new_with(
+        genesis, 20, false,
+    )));
+    let writer = {
+        let shared = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            for i in 1..500 {
+                shared.insert(ReorgNode::new(i, i, 0, i - 1, ()), None);
+                // A competing branch that stays one node behind
+                shared.insert(
+                    ReorgNode::new(10000 + i, i, 0, if i == 1 { 0 } else { 10000 + i - 1 }, ()),
+                    None,
+                );
+            }
+        })
+    };
+    let readers: Vec<_> = (0..3)
+        .map(|_| {
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || {
+                let mut last = 0;
+                while last < 499 {
+                    let chain = shared.chain();
+                    assert!(chain.height() >= last);
+                    assert_eq!(chain.keys().last(), Some(chain.head()));
+                    last = chain.height();
+                }
+            })
+        })
+        .collect();
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    // On a tie the first seen branch stays canonical
+    assert_eq!(shared.head(), 499);
+    assert_eq!(shared.chain().keys().len(), 21);
+    assert!(shared.chain().contains(&480));
+    // A fork that overtakes the head becomes canonical
+    shared.insert(ReorgNode::new(20490, 490, 0, 489, ()), None);
+    for i in 491..=500 {
+        shared.insert(ReorgNode::new(20000 + i, i, 0, 20000 + i - 1, ()), None);
+    }
+    assert_eq!(shared.head(), 20500);
+    assert!(!shared.chain().contains(&490));
+    assert_eq!(shared.read(|organizer| organizer.canonical_head()), 20500);
+    let organizer = Arc::try_unwrap(shared).ok().unwrap().into_inner();
+    assert_eq!(organizer.height(), 500);
+}
+
+#[test]
+fn actor_test() {
+    let genesis = ReorgNode::new(0, 0, 0, 0, ());
+    let actor = Actor::spawn(Organizer::<u64, ()>::new_with(genesis, 3, false));
+    let handle = actor.handle();
+    let events = handle.subscribe().unwrap();
+    for i in 1..4 {
+        handle
+            .submit(ReorgNode::new(i, i, 0, i - 1, ()), None)
+            .unwrap();
+    }
+    // A fork that overtakes the head
+    handle
+        .insert(ReorgNode::new(12, 2, 0, 1, ()), None)
+        .unwrap();
+    handle
+        .insert(ReorgNode::new(13, 3, 0, 12, ()), None)
+        .unwrap();
+    assert_eq!(
+        handle
+            .insert(ReorgNode::new(14, 4, 0, 13, ()), None)
+            .unwrap(),
+        InsertOutcome::Attached { finalized: vec![1] }
+    );
+    let received: Vec<Event<u64>> = events.try_iter().collect();
+    assert_eq!(
+        received,
+        vec![
+            Event::NewHead(1),
+            Event::NewHead(2),
+            Event::NewHead(3),
+            Event::Finalized(1),
+            Event::Reorg {
+                old_head: 3,
+                new_head: 14
+            },
+        ]
+    );
+    let abandoned = handle.finalize(12).unwrap().unwrap();
+    let mut abandoned: Vec<u64> = abandoned.iter().map(|node| *node.key()).collect();
+    abandoned.sort_unstable();
+    assert_eq!(abandoned, vec![2, 3]);
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![
+            Event::Finalized(12),
+            Event::Abandoned(2),
+            Event::Abandoned(3)
+        ]
+    );
+    assert_eq!(
+        handle.query(|organizer| *organizer.root().key()).unwrap(),
+        12
+    );
+    let organizer = actor.stop();
+    assert_eq!(organizer.height(), 4);
+    assert_eq!(handle.query(|organizer| organizer.height()), Err(Stopped));
+}
+
+#[test]
+fn batch_test() {
+    let genesis = ReorgNode::new(utoa(0), 0, 0, utoa(999999999), ());
+    let mut cb = Organizer::new_with(genesis, 255, false);
+    let journal = SharedBuffer::default();
+    cb.start_journal(journal.clone()).unwrap();
+    // The nodes arrive in reverse, with a short fork and a few invalid ones
+    let mut nodes: Vec<ReorgNode<[u8; 32], ()>> = (1..2000)
+        .rev()
+        .map(|i| ReorgNode::new(utoa(i), i, 0, utoa(i - 1), ()))
+        .collect();
+    nodes.push(ReorgNode::new(utoa(5000), 10, 0, utoa(9), ()));
+    nodes.push(ReorgNode::new(utoa(5001), 11, 0, utoa(5000), ()));
+    nodes.push(ReorgNode::new(utoa(6000), 0, 0, utoa(0), ()));
+    nodes.push(ReorgNode::new(utoa(6001), 3000, 0, utoa(5999), ()));
+    let outcome = cb.insert_batch(nodes, None);
+    assert_eq!(outcome.attached, 2001);
+    assert_eq!(outcome.buffered, 1);
+    assert_eq!(outcome.rejected, vec![(utoa(6000), Rejection::TooOld)]);
+    assert_eq!(outcome.finalized.len(), 1999 - 255);
+    assert_eq!(cb.root().key(), &utoa(1999 - 255));
+    assert_eq!(cb.height(), 1999);
+    assert_eq!(cb.check_height_to_key_diff(), vec![utoa(1999 - 255)]);
+    // The organizer ends up in the same state as with the nodes inserted one by one
+    let filled = create_test_filled();
+    assert_eq!(cb.root().key(), filled.root().key());
+    assert_eq!(cb.canonical_chain(), filled.canonical_chain());
+    cb.sync_journal().unwrap();
+    let bytes = journal.0.lock().unwrap().clone();
+    let (replayed, _) = Organizer::<[u8; 32], ()>::replay(bytes.as_slice()).unwrap();
+    assert_eq!(replayed.canonical_chain(), cb.canonical_chain());
+}
+
+#[test]
+fn stats_test() {
+    let mut cb = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 10, false);
+    for i in 1..=8 {
+        cb.insert(ReorgNode::new(i, i, 0, i - 1, ()), None);
+    }
+    // A fork from the third node overtakes the canonical chain, leaving five nodes behind
+    for i in 4..=9 {
+        cb.insert(
+            ReorgNode::new(100 + i, i, 0, if i == 4 { 3 } else { 100 + i - 1 }, ()),
+            None,
+        );
+    }
+    // Orphans that wait in the buffer until they expire, one of them on a side branch,
+    // and a refused insert
+    cb.insert(ReorgNode::new(500, 12, 0, 499, ()), None);
+    cb.insert(ReorgNode::new(600, 5, 0, 599, ()), None);
+    cb.insert(ReorgNode::new(700, 0, 0, 0, ()), None);
+    let stats = cb.stats();
+    assert_eq!(stats.nodes, 15);
+    assert_eq!(stats.buffered, 2);
+    assert_eq!(stats.tips, 2);
+    assert_eq!(stats.forks, 1);
+    assert_eq!(stats.max_fork_length, 5);
+    assert_eq!(stats.reorgs, 1);
+    assert_eq!(stats.deepest_reorg, 5);
+    assert_eq!(stats.rejected(Rejection::TooOld), 1);
+    assert_eq!(stats.rejected(Rejection::Invalid), 0);
+    for i in 10..=25 {
+        cb.insert(ReorgNode::new(100 + i, i, 0, 100 + i - 1, ()), None);
+    }
+    let stats = cb.stats();
+    assert_eq!(stats.pruned, 5);
+    assert_eq!(stats.orphans_expired, 2);
+    assert_eq!(stats.buffered, 0);
+    assert_eq!((stats.tips, stats.forks, stats.max_fork_length), (1, 0, 0));
+    assert_eq!(stats.nodes, 11);
+}
+
+#[test]
+fn render_test() {
+    let mut cb = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 10, false);
+    for i in 1..=3 {
+        cb.insert(ReorgNode::new(i, i, 0, i - 1, ()), None);
+    }
+    cb.insert(ReorgNode::new(12, 2, 0, 1, ()), None);
+    cb.insert(ReorgNode::new(22, 2, 0, 1, ()), None);
+    cb.insert(ReorgNode::new(9, 9, 0, 8, ()), None);
+    let mut tree = String::new();
+    cb.write_tree(&mut tree).unwrap();
+    assert_eq!(
+        tree,
+        "0 (height 0) *\n\
+         1 (height 1) *\n\
+         |-- 2 (height 2) *\n\
+         |   3 (height 3) *\n\
+         |-- 12 (height 2)\n\
+         `-- 22 (height 2)\n\
+         buffer:\n    9 (height 9) waiting for 8\n"
+    );
+    let dot = cb.to_dot();
+    assert!(dot.starts_with("digraph reorg {\n"));
+    assert!(dot.contains("    \"0\" [label=\"0\\nheight 0\", style=bold, color=red];\n"));
+    assert!(dot.contains("    \"1\" -> \"12\";\n"));
+    assert!(dot.contains("    \"2\" -> \"3\" [color=red, penwidth=2];\n"));
+    assert!(dot.contains("        \"9\" [label=\"9\\nheight 9\", style=dashed];\n"));
+    assert!(!dot.contains("\"8\" -> \"9\""));
+    // Quotes in the keys are escaped
+    let mut cb = Organizer::<&str, ()>::new_with(ReorgNode::new("a\"b", 0, 0, "", ()), 10, false);
+    cb.insert(ReorgNode::new("c", 1, 0, "a\"b", ()), None);
+    assert!(cb
+        .to_dot()
+        .contains("\"\\\"a\\\\\\\"b\\\"\" -> \"\\\"c\\\"\""));
+}
+
+/// Sink that refuses every write.
+struct BrokenSink;
+
+impl Write for BrokenSink {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_nodes_test() {
+    let mut cb = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 10, false);
+    cb.insert(ReorgNode::new(20, 2, 0, 1, ()), None);
+    cb.insert(ReorgNode::new(1, 1, 0, 0, ()), None);
+    cb.insert(ReorgNode::new(10, 2, 0, 1, ()), None);
+    cb.insert(ReorgNode::new(9, 2, 0, 1, ()), None);
+    let mut keys = Vec::new();
+    cb.write_node_keys(&mut keys).unwrap();
+    assert_eq!(String::from_utf8(keys).unwrap(), "1\n9\n10\n20\n");
+    let mut compact = String::new();
+    cb.fmt_nodes(&mut compact, true).unwrap();
+    assert_eq!(compact.lines().count(), 4);
+    assert!(compact.starts_with("1 height 1 value 0 parent 0 children [20, 10, 9] meta ()\n"));
+    let mut full = Vec::new();
+    cb.write_nodes(&mut full, false).unwrap();
+    let full = String::from_utf8(full).unwrap();
+    assert_eq!(full.matches(">Key: ").count(), 4);
+    assert!(full.find(">Key: 10").unwrap() < full.find(">Key: 20").unwrap());
+    let error = cb.write_nodes(&mut BrokenSink, true).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    // Keys that are not ordered can still be listed on the standard output
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Unordered(u8);
+    let mut cb = Organizer::<Unordered, ()>::new_with(
+        ReorgNode::new(Unordered(0), 0, 0, Unordered(0), ()),
+        10,
+        false,
+    );
+    cb.insert(ReorgNode::new(Unordered(2), 1, 0, Unordered(0), ()), None);
+    cb.insert(ReorgNode::new(Unordered(1), 1, 0, Unordered(0), ()), None);
+    cb.list_node_keyes();
     cb.list_nodes();
-    // assert!(false)
+}
+
+#[test]
+fn sim_test() {
+    let path = std::env::temp_dir().join(format!("abandoning_reorg_sim_{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "root g 0\n\
+         insert a1 1 0 g\n\
+         insert a2 2 0 a1\n\
+         insert b2 2 0 a1 # a competing node\n\
+         insert b3 3 0 b2\n\
+         dump\n\
+         insert b4 4 0 b3\n\
+         finalize b3\n",
+    )
+    .unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_reorg-sim"))
+        .args(["--depth", "3"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("insert b3\n  attached\n  reorg from a2 to b3\n"));
+    assert!(stdout.contains("  abandoned a2\n"));
+    assert!(stdout.contains(
+        "g (height 0) *\n\
+         a1 (height 1) *\n\
+         |-- a2 (height 2)\n\
+         `-- b2 (height 2) *\n    \
+         b3 (height 3) *\n"
+    ));
+    assert!(stdout.ends_with("final tree\nb3 (height 3) *\nb4 (height 4) *\n"));
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_reorg-sim"))
+        .arg(std::env::temp_dir().join("abandoning_reorg_sim_missing"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn workload_test() {
+    let config = WorkloadConfig {
+        nodes: 2000,
+        ..WorkloadConfig::default()
+    };
+    let keys = |seed| {
+        Workload::new(config, seed)
+            .map(|node| *node.key())
+            .collect::<Vec<u64>>()
+    };
+    let mut sorted = keys(3);
+    assert_eq!(sorted, keys(3));
+    assert_ne!(sorted, keys(4));
+    // Every generated node is delivered exactly once
+    sorted.sort_unstable();
+    assert_eq!(sorted, (1..=2000).collect::<Vec<u64>>());
+    // Some nodes arrive before their parents
+    let mut seen = std::collections::HashSet::new();
+    seen.insert(0);
+    let mut early = 0;
+    for node in Workload::new(config, 3) {
+        if !seen.contains(node.parent()) {
+            early += 1;
+        }
+        seen.insert(*node.key());
+    }
+    assert!(early > 0);
+    let mut cb = Organizer::new_with(Workload::genesis(), 64, false);
+    for node in Workload::new(config, 3) {
+        cb.insert(node, None);
+    }
+    assert!(cb.stats().reorgs > 0);
+    assert_eq!(cb.check_height_to_key_diff(), vec![*cb.root().key()]);
+}
+
+#[test]
+fn order_test() {
+    let config = WorkloadConfig {
+        nodes: 500,
+        fork_percent: 30,
+        ..WorkloadConfig::default()
+    };
+    // Parents are generated before their children, so the keys give the order of generation
+    let mut in_order: Vec<ReorgNode<u64, ()>> = Workload::new(config, 11).collect();
+    in_order.sort_by_key(|node| *node.key());
+    let mut shuffled = in_order.clone();
+    let mut rng = SplitMix64::new(11);
+    for i in (1..shuffled.len()).rev() {
+        shuffled.swap(i, rng.below(i as u64 + 1) as usize);
+    }
+    // Child first delivery of a side branch
+    let mut reversed = in_order.clone();
+    reversed.reverse();
+    let organize = |nodes: Vec<ReorgNode<u64, ()>>| {
+        let mut cb = Organizer::new_with(Workload::genesis(), 1000, false);
+        for node in nodes {
+            assert!(!matches!(cb.insert(node, None), InsertOutcome::Rejected(_)));
+        }
+        cb
+    };
+    let expected = organize(in_order);
+    for nodes in [Workload::new(config, 11).collect(), shuffled, reversed] {
+        let cb = organize(nodes);
+        assert_eq!(cb.stats().buffered, 0);
+        assert_eq!(cb.height(), expected.height());
+        for key in 1..=500 {
+            assert_eq!(
+                cb.get(&key).unwrap().parent(),
+                expected.get(&key).unwrap().parent()
+            );
+        }
+    }
+}
+
+#[test]
+fn arrival_test() {
+    let mut cb = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 10, false);
+    let journal = SharedBuffer::default();
+    cb.start_journal(journal.clone()).unwrap();
+    // The head of the second branch arrives first, but attaches last
+    cb.insert_from(ReorgNode::new(23, 3, 0, 22, ()), Some(2), Some(1000), None);
+    for i in 1..=3 {
+        cb.insert_from(
+            ReorgNode::new(i, i, 0, i - 1, ()),
+            Some(1),
+            Some(1000 + i),
+            None,
+        );
+    }
+    assert_eq!(cb.canonical_head(), 3);
+    // Refused nodes are not numbered
+    cb.insert(ReorgNode::new(50, 0, 0, 0, ()), None);
+    cb.insert(ReorgNode::new(22, 2, 0, 21, ()), None);
+    cb.insert(ReorgNode::new(21, 1, 0, 0, ()), None);
+    assert_eq!(cb.arrival(&22).unwrap().sequence(), 5);
+    assert_eq!(cb.arrival(&21).unwrap().sequence(), 6);
+    assert_eq!(cb.arrival(&21).unwrap().source(), None);
+    let arrival = *cb.get(&23).unwrap().arrival();
+    assert_eq!(arrival.sequence(), 1);
+    assert_eq!(
+        (arrival.source(), arrival.timestamp()),
+        (Some(2), Some(1000))
+    );
+    // Of the equally long branches the one whose head was seen first leads
+    assert_eq!(cb.canonical_head(), 23);
+    assert_eq!(cb.find_longest_branch(None), 21);
+    assert_eq!(cb.root().arrival().sequence(), 0);
+    let mut restored = Organizer::<u64, ()>::restore(&cb.snapshot()).unwrap();
+    assert_eq!(restored.arrival(&23), Some(arrival));
+    restored.insert(ReorgNode::new(4, 4, 0, 3, ()), None);
+    assert_eq!(restored.arrival(&4).unwrap().sequence(), 7);
+    cb.sync_journal().unwrap();
+    let bytes = journal.0.lock().unwrap().clone();
+    let (replayed, _) = Organizer::<u64, ()>::replay(bytes.as_slice()).unwrap();
+    for key in [1, 2, 3, 21, 22, 23] {
+        assert_eq!(replayed.arrival(&key), cb.arrival(&key));
+    }
+    assert_eq!(replayed.canonical_head(), 23);
+}
+
+/// Observer recording the sources that reached the misbehavior threshold.
+struct Reporter(Arc<Mutex<Vec<(u64, u64)>>>);
+
+impl Observer<u64, ()> for Reporter {
+    fn misbehaved(&mut self, source: u64, misbehavior: &Misbehavior) {
+        self.0.lock().unwrap().push((source, misbehavior.total()));
+    }
+}
+
+#[test]
+fn misbehavior_test() {
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let mut cb = Organizer::builder()
+        .depth(5)
+        .misbehavior_threshold(3)
+        .validator(|node: &ReorgNode<u64, ()>| node.value() < 100)
+        .observer(Reporter(reported.clone()))
+        .root(ReorgNode::new(0, 0, 0, 0, ()))
+        .build()
+        .unwrap();
+    for i in 1..=10 {
+        cb.insert_from(ReorgNode::new(i, i, 0, i - 1, ()), Some(1), None, None);
+    }
+    assert_eq!(
+        cb.insert_from(ReorgNode::new(100, 11, 100, 10, ()), Some(9), None, None),
+        InsertOutcome::Rejected(Rejection::Invalid)
+    );
+    assert_eq!(
+        cb.insert_from(ReorgNode::new(10, 10, 0, 9, ()), Some(9), None, None),
+        InsertOutcome::Rejected(Rejection::Duplicate)
+    );
+    assert!(reported.lock().unwrap().is_empty());
+    assert_eq!(
+        cb.insert_from(ReorgNode::new(101, 2, 0, 1, ()), Some(9), None, None),
+        InsertOutcome::Rejected(Rejection::TooOld)
+    );
+    // The threshold is reported once, when it is reached
+    cb.insert_from(ReorgNode::new(102, 2, 0, 1, ()), Some(9), None, None);
+    assert_eq!(*reported.lock().unwrap(), vec![(9, 3)]);
+    assert_eq!(
+        cb.misbehavior(9),
+        Misbehavior {
+            invalid: 1,
+            duplicate: 1,
+            too_old: 2,
+            expired_orphans: 0
+        }
+    );
+    // An orphan of another source expires as the tree moves past it
+    cb.insert_from(ReorgNode::new(200, 8, 0, 199, ()), Some(5), None, None);
+    for i in 11..=14 {
+        cb.insert_from(ReorgNode::new(i, i, 0, i - 1, ()), Some(1), None, None);
+    }
+    assert_eq!(cb.misbehavior(5).expired_orphans, 1);
+    // Submissions without a source, and of well behaving sources, are not tallied
+    cb.insert(ReorgNode::new(14, 14, 0, 13, ()), None);
+    assert_eq!(cb.misbehavior(1).total(), 0);
+    assert_eq!(cb.stats().rejected(Rejection::Duplicate), 2);
+    cb.forgive(9);
+    assert_eq!(cb.misbehavior(9).total(), 0);
+    for key in 1..=3 {
+        cb.insert_from(
+            ReorgNode::new(key, key, 0, key - 1, ()),
+            Some(9),
+            None,
+            None,
+        );
+    }
+    assert_eq!(*reported.lock().unwrap(), vec![(9, 3), (9, 3)]);
+}
+
+/// 256 bit weight, the most significant word first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Work([u64; 4]);
+
+impl Weight for Work {
+    type Sum = Work;
+
+    fn zero() -> Work {
+        Work::default()
+    }
+
+    fn add(sum: &Work, weight: &Work) -> Work {
+        let mut words = [0; 4];
+        let mut carry = 0;
+        for i in (0..4).rev() {
+            let wide = u128::from(sum.0[i]) + u128::from(weight.0[i]) + carry;
+            words[i] = wide as u64;
+            carry = wide >> 64;
+        }
+        Work(words)
+    }
+
+    fn sub(sum: &Work, other: &Work) -> Work {
+        let mut words = [0; 4];
+        let mut borrow = 0;
+        for i in (0..4).rev() {
+            let (word, first) = sum.0[i].overflowing_sub(other.0[i]);
+            let (word, second) = word.overflowing_sub(borrow);
+            words[i] = word;
+            borrow = u64::from(first || second);
+        }
+        Work(words)
+    }
+}
+
+impl Codec for Work {
+    fn encode(&self, output: &mut Vec<u8>) {
+        for word in &self.0 {
+            word.encode(output);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<Self, SnapshotError> {
+        let mut words = [0; 4];
+        for word in &mut words {
+            *word = u64::decode(input)?;
+        }
+        Ok(Work(words))
+    }
+}
+
+#[test]
+fn weight_test() {
+    let mut cb =
+        Organizer::<u64, (), MemoryStore<u64, ()>, Work>::with_store(MemoryStore::default(), 3);
+    cb.set_value_based(true);
+    cb.init(ReorgNode::new(0, 0, Work::default(), 0, ()));
+    // A long branch of light nodes, whose sum carries into the second word
+    for i in 1..=4 {
+        cb.insert(
+            ReorgNode::new(i, i, Work([0, 0, 0, u64::MAX]), i - 1, ()),
+            None,
+        );
+    }
+    // A competing branch of a single heavy node
+    cb.insert(ReorgNode::new(12, 2, Work([0, 0, 0, 0]), 1, ()), None);
+    cb.insert(ReorgNode::new(13, 3, Work([0, 0, 0, 0]), 12, ()), None);
+    cb.insert(ReorgNode::new(14, 4, Work([0, 1, 0, 0]), 13, ()), None);
+    assert_eq!(cb.canonical_head(), 14);
+    assert_eq!(cb.value_above_root(&4), Some(Work([0, 0, 2, u64::MAX - 2])));
+    assert_eq!(cb.value_above_root(&14), Some(Work([0, 1, 0, 0])));
+    assert_eq!(
+        *cb.get(&4).unwrap().cumulative_value(),
+        Work([0, 0, 3, u64::MAX - 3])
+    );
+    assert_eq!(cb.get(&14).unwrap().weight(), &Work([0, 1, 0, 0]));
+    // The root advances into the heavier branch
+    cb.insert(ReorgNode::new(15, 5, Work::default(), 14, ()), None);
+    assert_eq!(cb.root().key(), &12);
+    assert!(cb.get(&4).is_none());
+    let restored =
+        Organizer::<u64, (), MemoryStore<u64, ()>, Work>::restore(&cb.snapshot()).unwrap();
+    assert_eq!(restored.canonical_head(), 15);
+    assert_eq!(restored.value_above_root(&15), Some(Work([0, 1, 0, 0])));
+    assert_eq!(
+        *restored.get(&15).unwrap().cumulative_value(),
+        Work([0, 1, 0, u64::MAX])
+    );
+}
+
+#[test]
+fn offset_height_test() {
+    // Slots starting far from zero, with two of every three slots skipped
+    let mut cb = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 1_000_000, 0, 0, ()), 10, false);
+    for i in 1..=10 {
+        let outcome = cb.insert(ReorgNode::new(i, 1_000_000 + 3 * i, 0, i - 1, ()), None);
+        assert!(matches!(outcome, InsertOutcome::Attached { .. }));
+    }
+    assert_eq!(cb.height(), 1_000_030);
+    assert_eq!(cb.root().key(), &7);
+    assert_eq!(cb.check_height_to_key_diff(), vec![7]);
+    // A child has to be higher than its parent
+    assert_eq!(
+        cb.insert(ReorgNode::new(11, 1_000_030, 0, 10, ()), None),
+        InsertOutcome::Rejected(Rejection::NotAboveParent)
+    );
+    // An orphan is dropped when its parent turns out not to be below it
+    assert_eq!(
+        cb.insert_from(
+            ReorgNode::new(13, 1_000_032, 0, 12, ()),
+            Some(4),
+            None,
+            None
+        ),
+        InsertOutcome::Buffered
+    );
+    cb.insert(ReorgNode::new(12, 1_000_033, 0, 10, ()), None);
+    assert_eq!(cb.stats().buffered, 0);
+    assert_eq!(cb.stats().rejected(Rejection::NotAboveParent), 2);
+    assert_eq!(cb.misbehavior(4).invalid, 1);
+    assert!(cb.get(&13).is_none());
+    assert_eq!(cb.pop_head().len(), 1);
+    assert_eq!(cb.height(), 1_000_030);
+    // The same holds for an orphan sorted before its parent in a batch
+    let mut batch = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 10, false);
+    let outcome = batch.insert_batch(
+        vec![
+            ReorgNode::new(6, 2, 0, 5, ()),
+            ReorgNode::new(5, 5, 0, 0, ()),
+        ],
+        None,
+    );
+    assert_eq!(outcome.attached, 1);
+    assert_eq!(outcome.rejected, vec![(6, Rejection::NotAboveParent)]);
+    assert_eq!(batch.stats().buffered, 0);
+
+    // The placeholder of a forest stays below the lowest candidate root
+    let mut forest = Organizer::<u64, ()>::new_forest(10, false);
+    assert_eq!(
+        forest.insert(ReorgNode::new(1, 0, 0, 99, ()), None),
+        InsertOutcome::Rejected(Rejection::TooOld)
+    );
+    forest.insert(ReorgNode::new(2, 1, 0, 1, ()), None);
+    assert_eq!(forest.root().height(), 0);
+
+    // Heights at the top of the range do not overflow
+    let mut top =
+        Organizer::<u64, ()>::new_with(ReorgNode::new(0, u64::MAX - 2, 0, 0, ()), 10, false);
+    top.insert(ReorgNode::new(1, u64::MAX - 1, 0, 0, ()), None);
+    top.insert(ReorgNode::new(2, u64::MAX, 0, 1, ()), None);
+    top.insert(ReorgNode::new(3, u64::MAX - 1, 0, 5, ()), None);
+    assert_eq!(top.finalize(&2).map(|abandoned| abandoned.len()), Some(0));
+    assert_eq!(top.root().height(), u64::MAX);
+    assert_eq!(top.stats().buffered, 0);
+}
+
+#[test]
+fn slot_test() {
+    let mut cb = Organizer::<u64, ()>::builder()
+        .depth(10)
+        .slot_based()
+        .root(ReorgNode::new(0, 0, 0, 0, ()))
+        .build()
+        .unwrap();
+    // A dense branch and a sparse branch reaching higher slots
+    for i in 1..=6 {
+        cb.insert(ReorgNode::new(i, i, 0, i - 1, ()), None);
+    }
+    cb.insert(ReorgNode::new(21, 2, 0, 0, ()), None);
+    cb.insert(ReorgNode::new(22, 5, 0, 21, ()), None);
+    cb.insert(ReorgNode::new(23, 9, 0, 22, ()), None);
+    assert_eq!(cb.height(), 9);
+    assert_eq!(cb.canonical_head(), 6);
+    assert_eq!(cb.stats().reorgs, 0);
+    cb.set_fork_choice(ForkChoice::MostSlots);
+    assert_eq!(cb.canonical_head(), 23);
+    cb.set_fork_choice(ForkChoice::Longest);
+    // Outside of the slot based mode only the highest node leads
+    let mut dense = Organizer::<u64, ()>::new_with(ReorgNode::new(0, 0, 0, 0, ()), 10, false);
+    for node in [(1, 1, 0), (2, 2, 1), (3, 3, 2), (21, 2, 0), (22, 5, 21)] {
+        dense.insert(ReorgNode::new(node.0, node.1, 0, node.2, ()), None);
+    }
+    assert_eq!(dense.canonical_head(), 22);
+    // The window counts slots from the canonical head, a node far ahead on the losing
+    // branch does not move the root
+    let snapshot = cb.snapshot();
+    assert_eq!(
+        cb.insert(ReorgNode::new(24, 14, 0, 23, ()), None),
+        InsertOutcome::Attached { finalized: vec![] }
+    );
+    assert_eq!(cb.root().key(), &0);
+    assert_eq!(cb.height(), 14);
+    assert_eq!(cb.canonical_head(), 6);
+    for i in 7..=11 {
+        cb.insert(ReorgNode::new(i, i, 0, i - 1, ()), None);
+    }
+    // Once the canonical head is far enough, the root advances into its branch
+    assert_eq!(cb.root().key(), &1);
+    assert!(cb.get(&24).is_none());
+    assert_eq!(cb.height(), 11);
+    let mut restored = Organizer::<u64, ()>::restore(&snapshot).unwrap();
+    assert!(restored.is_slot_based());
+    restored.set_fork_choice(ForkChoice::MostSlots);
+    restored.insert(ReorgNode::new(24, 14, 0, 23, ()), None);
+    assert_eq!(restored.root().key(), &22);
+    assert!(restored.get(&6).is_none());
+    assert_eq!(restored.canonical_chain(), vec![22, 23, 24]);
+    // Every height below the root is gone from the index
+    assert_eq!(restored.check_height_to_key_diff(), vec![22]);
+    assert_eq!(restored.stats().nodes, 3);
+    assert_eq!(
+        Organizer::<u64, ()>::restore(&restored.snapshot())
+            .unwrap()
+            .fork_choice(),
+        ForkChoice::MostSlots
+    );
 }